@@ -5,14 +5,14 @@ use skakoui::{Board, Move};
 use std::convert::TryInto;
 use std::fmt;
 
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode};
 
 fn number_of_moves_is_correct_for_initial_position(c: &mut Criterion) {
     run_perft(
         "perft_init",
         c,
         Board::default(),
-        vec![20, 400, 8902, 197_281, 4_865_609],
+        vec![20, 400, 8902, 197_281, 4_865_609, 119_060_324],
     );
 }
 
@@ -23,7 +23,7 @@ fn number_of_moves_is_correct_for_kiwipete_position(c: &mut Criterion) {
         "perft_kiwipete",
         c,
         "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
-        vec![48, 2039, 97_862, 4_085_603],
+        vec![48, 2039, 97_862, 4_085_603, 193_690_690],
     );
 }
 
@@ -41,15 +41,20 @@ fn run_perft(
     expected_moves_at_depth: Vec<usize>,
 ) {
     let mut board = board.try_into().unwrap();
+    let mut group = c.benchmark_group(name);
+    // for long-running benchmarks
+    group.sampling_mode(SamplingMode::Flat).sample_size(10);
 
     for (depth, expected_moves) in expected_moves_at_depth.iter().enumerate() {
         let depth = depth + 1;
-        c.bench_with_input(BenchmarkId::new(name, depth), &depth, |b, depth| {
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, depth| {
             b.iter(|| {
                 expect_moves(&mut board, *depth, *expected_moves);
             });
         });
     }
+
+    group.finish();
 }
 
 fn expect_moves(board: &mut Board, depth: usize, expected_moves: usize) {
@@ -1,4 +1,4 @@
-use skakoui::{Board, Move, PlayedMove, Searcher};
+use skakoui::{Board, GameState, Move, PlayedMove, SearchLimits, Searcher};
 
 use criterion::measurement::Measurement;
 use criterion::{
@@ -14,8 +14,7 @@ fn searcher_can_find_mate(c: &mut Criterion) {
 
     test_find_mates("in1", &mut group, mate_in_1s());
     test_find_mates("in2", &mut group, mate_in_2s());
-    // TODO: too slow for all of these
-    test_find_mates("in3", &mut group, mate_in_3s().take(10));
+    test_find_mates("in3", &mut group, mate_in_3s());
 
     group.finish();
 }
@@ -46,13 +45,19 @@ fn test_find_mates<M: Measurement>(
     });
 }
 
-fn test_find_mate(searcher: &mut Searcher, mut board: Board, mating_moves: &[Move]) {
+fn test_find_mate(searcher: &mut Searcher, board: Board, mating_moves: &[Move]) {
     let n = mating_moves.len();
 
-    searcher.go(&board, Some(n as u16 + 1));
-    searcher.wait();
+    let result = searcher
+        .search(
+            &GameState::new(board.clone()),
+            SearchLimits::depth(n as u16 + 1),
+        )
+        .unwrap();
 
-    let mut moves = searcher.principal_variation(&mut board);
+    let mate_in = result.score.mate_in();
+
+    let mut moves = result.pv;
     moves.truncate(n);
 
     let mut test_board = board.clone();
@@ -69,10 +74,20 @@ fn test_find_mate(searcher: &mut Searcher, mut board: Board, mating_moves: &[Mov
         mating_moves.iter().join(" "),
         moves.iter().join(" ")
     );
+
+    let expected_mate_in = (n as i16 + 1) / 2;
+    assert_eq!(
+        mate_in,
+        Some(expected_mate_in),
+        "{}\n{}\nExpected mate in {}",
+        board,
+        board.fen_url(),
+        expected_mate_in
+    );
 }
 
 fn mate_in_1s() -> impl Iterator<Item = (Board, Vec<Move>)> {
-    mate_in_2s().into_iter().map(|(mut board, moves)| {
+    mate_in_2s().map(|(mut board, moves)| {
         board.make_move(moves[0]);
         board.make_move(moves[1]);
         (board, vec![moves[2]])
@@ -1,7 +1,8 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 
-use skakoui::Board;
+use skakoui::GameState;
 use skakoui::Move;
+use skakoui::SearchLimits;
 use skakoui::Searcher;
 use std::collections::HashSet;
 use std::default::Default;
@@ -15,27 +16,33 @@ fn main() {
     let stdin = io::stdin();
     let lock = stdin.lock();
 
-    let mut board = Board::default();
-    println!("{}", board);
+    let mut state = GameState::default();
+    println!("{}", state.board);
 
     let mut white = Computer::default();
     let mut black = Human::new(lock);
 
     loop {
-        play(&mut white, &mut board);
-        play(&mut black, &mut board);
+        play(&mut white, &mut state);
+        play(&mut black, &mut state);
     }
 }
 
-fn play<A: Agent>(agent: &mut A, board: &mut Board) {
+fn play<A: Agent>(agent: &mut A, state: &mut GameState) {
+    if let Some(outcome) = state.outcome() {
+        println!();
+        println!("Game Over! {:?}", outcome);
+        std::process::exit(0)
+    }
+
     println!();
     print!("{}: ", A::NAME);
     io::stdout().flush().expect("Could not flush stdout");
-    if let Some(mov) = agent.get_move(board) {
+    if let Some(mov) = agent.get_move(state) {
         println!("{}", mov);
-        board.make_move(mov);
+        state.push_move(mov);
         println!();
-        println!("{}", board);
+        println!("{}", state.board);
     } else {
         println!("Game Over!");
         std::process::exit(0) // TODO: don't just exit on game over ¯\_(ツ)_/¯
@@ -45,7 +52,7 @@ fn play<A: Agent>(agent: &mut A, board: &mut Board) {
 trait Agent {
     const NAME: &'static str;
 
-    fn get_move(&mut self, board: &mut Board) -> Option<Move>;
+    fn get_move(&mut self, state: &mut GameState) -> Option<Move>;
 }
 
 #[derive(Default)]
@@ -56,12 +63,11 @@ struct Computer {
 impl Agent for Computer {
     const NAME: &'static str = "Computer";
 
-    fn get_move(&mut self, board: &mut Board) -> Option<Move> {
-        self.searcher.go(board, None);
-        std::thread::sleep(Duration::from_secs(1));
-        self.searcher.stop();
-        let pv = self.searcher.principal_variation(board);
-        pv.first().copied()
+    fn get_move(&mut self, state: &mut GameState) -> Option<Move> {
+        self.searcher
+            .search(state, SearchLimits::movetime(Duration::from_secs(1)))
+            .expect("the search is limited by time")
+            .best_move()
     }
 }
 
@@ -80,8 +86,8 @@ impl<B: BufRead> Human<B> {
 impl<B: BufRead> Agent for Human<B> {
     const NAME: &'static str = "Player";
 
-    fn get_move(&mut self, board: &mut Board) -> Option<Move> {
-        let valid_moves: HashSet<Move> = board.moves().collect();
+    fn get_move(&mut self, state: &mut GameState) -> Option<Move> {
+        let valid_moves: HashSet<Move> = state.board.moves().collect();
 
         loop {
             let line = self.input.next().unwrap().unwrap();
@@ -1,134 +1,350 @@
-use crate::Command::Perft;
+use crate::Command::{Perft, PerftSuite};
 use anyhow::anyhow;
-use skakoui::{Board, Move, PlayerV, Searcher};
+use skakoui::{
+    perft_suite, Board, Bound, Clock, GameState, Move, PerftTable, PlayerV, Score, SearchInfo,
+    SearchLimits, SearchResult, Searcher,
+};
 use std::error::Error;
 use std::fmt;
 use std::io::{BufRead, BufReader, Write};
 use std::str::FromStr;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
 use std::time::Duration;
-use Command::{Go, IsReady, PonderHit, Position, Quit, Stop};
-use Info::PV;
+use Command::{Go, IsReady, PonderHit, Position, Quit, SetOption, Stop};
+use Info::{Depth, HashFull, LowerBound, MultiPV, Nodes, Nps, SelDepth, Time, UpperBound, PV};
 use Message::{BestMove, ReadyOk, UciOk};
-use OptionType::{Button, Check, Combo, Spin, String};
+use OptionType::{Button, Check, Spin};
 use ID::{Author, Name};
 
+const DEFAULT_HASH_MB: usize = 16;
+const MAX_HASH_MB: usize = 4096;
+const DEFAULT_THREADS: u32 = 1;
+const MAX_THREADS: u32 = 64;
+const MAX_MULTI_PV: u16 = 256;
+/// Size of the table perft uses to skip positions it has already counted. Kept apart from the
+/// search's `Hash`, so perft doesn't double the memory we use.
+const PERFT_HASH_MB: usize = 16;
+
 fn main() -> Result<(), Box<dyn Error>> {
     let stdin = std::io::stdin();
     let stdout = std::io::stdout();
 
-    let reader = BufReader::new(stdin.lock());
+    let reader = BufReader::new(stdin);
 
     run(reader, &mut stdout.lock())?;
     Ok(())
 }
 
+/// Read lines on another thread, so we can keep responding while searching
+fn read_lines(input: impl BufRead + Send + 'static) -> Receiver<Result<String, std::io::Error>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        for line in input.lines() {
+            if tx.send(line).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// How often to check on the search while waiting for input
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Status {
+    Idle,
+    /// Searching, and we'll send the best move when the search stops on its own
+    Searching,
+    /// Searching until told to `stop`, or until `ponderhit` tells us the real limits
+    WaitingForStop,
+}
+
 struct Uci<W> {
     output: W,
-    board: Board,
-    ponder: Option<Move>,
+    state: GameState,
+    status: Status,
+    /// Limits to apply once the opponent plays the move we're pondering
+    ponder_limits: Option<SearchLimits>,
     searcher: Searcher,
+    /// Created on the first perft command, then kept since the counts never go stale
+    perft_table: Option<PerftTable>,
+    hash_mb: usize,
+    threads: u32,
+    multi_pv: u16,
 }
 
 impl<W: Write> Uci<W> {
-    fn run(&mut self, input: impl BufRead) -> Result<(), std::io::Error> {
-        for try_line in input.lines() {
-            let line = try_line?;
-            eprintln!("{}", line);
-
-            let command = match line.parse::<Command>() {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    continue;
+    fn run(&mut self, input: impl BufRead + Send + 'static) -> Result<(), std::io::Error> {
+        let lines = read_lines(input);
+
+        loop {
+            match lines.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => {
+                    if !self.handle(&line?)? {
+                        break;
+                    }
                 }
-            };
-
-            match command {
-                Command::Uci => {
-                    self.send(&Message::ID(Name, "skakoui"))?;
-                    self.send(&Message::ID(Author, "Felix Chapman"))?;
-                    self.send(&Message::option("Ponder", Check, "true"))?;
-                    self.send(&UciOk)?;
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    // No more input, so finish any search that will end on its own and exit
+                    while self.status == Status::Searching {
+                        thread::sleep(POLL_INTERVAL);
+                        self.poll_search()?;
+                    }
+                    break;
                 }
-                IsReady => {
-                    self.send(&ReadyOk)?;
+            }
+
+            self.poll_search()?;
+        }
+
+        // Don't leave threads searching in the background
+        if self.status != Status::Idle {
+            self.searcher.stop();
+        }
+
+        Ok(())
+    }
+
+    /// Respond to a line of input, returning `false` if we should quit
+    fn handle(&mut self, line: &str) -> Result<bool, std::io::Error> {
+        eprintln!("{}", line);
+
+        let command = match line.parse::<Command>() {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Ok(true);
+            }
+        };
+
+        match command {
+            Command::Uci => {
+                self.send(&Message::ID(Name, "skakoui"))?;
+                self.send(&Message::ID(Author, "Felix Chapman"))?;
+                self.send(&Message::option("Ponder", Check, "true"))?;
+                let hash = Spin {
+                    min: 1,
+                    max: MAX_HASH_MB as i64,
+                };
+                self.send(&Message::option("Hash", hash, &DEFAULT_HASH_MB.to_string()))?;
+                let threads = Spin {
+                    min: 1,
+                    max: MAX_THREADS as i64,
+                };
+                self.send(&Message::option(
+                    "Threads",
+                    threads,
+                    &DEFAULT_THREADS.to_string(),
+                ))?;
+                let multi_pv = Spin {
+                    min: 1,
+                    max: MAX_MULTI_PV as i64,
+                };
+                self.send(&Message::option("MultiPV", multi_pv, "1"))?;
+                self.send(&Message::Option {
+                    name: "Clear Hash",
+                    typ: Button,
+                    default: None,
+                })?;
+                self.send(&UciOk)?;
+            }
+            IsReady => {
+                self.send(&ReadyOk)?;
+            }
+            Quit => return Ok(false),
+            Position { board, moves } => {
+                if let Some(board) = board {
+                    self.state = GameState::new(*board);
                 }
-                Quit => break,
-                Position { board, moves } => {
-                    if let Some(board) = board {
-                        self.board = *board;
-                    }
 
-                    for mov in moves {
-                        self.board.make_move(mov);
-                    }
+                for mov in moves {
+                    self.state.push_move(mov);
                 }
-                PonderHit => {
-                    if let Some(ponder) = self.ponder.take() {
-                        self.board.make_move(ponder);
+            }
+            PonderHit => {
+                // The position we're pondering already includes the move the opponent played,
+                // so we can carry on searching with the real limits
+                if self.status == Status::WaitingForStop {
+                    if let Some(limits) = self.ponder_limits.take() {
+                        if limits.is_limited() {
+                            self.status = Status::Searching;
+                        }
+                        self.searcher.ponderhit(limits);
                     }
-                    self.go();
                 }
-                Go {
-                    movetime,
-                    wtime,
-                    btime,
-                    ponder,
-                } => {
-                    self.go();
+            }
+            Go {
+                mut limits,
+                white,
+                black,
+                ponder,
+            } => {
+                limits.clock = match self.state.board.player() {
+                    PlayerV::White => white,
+                    PlayerV::Black => black,
+                };
 
-                    if let Some(movetime) = movetime {
-                        std::thread::sleep(movetime);
-                        self.stop()?;
+                if ponder {
+                    // Keep searching until the opponent moves, then apply the limits
+                    self.ponder_limits = Some(limits);
+                    self.go(SearchLimits {
+                        infinite: true,
+                        ..SearchLimits::default()
+                    });
+                } else {
+                    self.go(limits);
+                }
+            }
+            Stop => {
+                if self.status != Status::Idle {
+                    self.searcher.stop();
+                    self.finish_search()?;
+                }
+            }
+            SetOption { name, value } => {
+                if let Err(e) = self.set_option(&name, value.as_deref()) {
+                    eprintln!("{}", e);
+                }
+            }
+            Perft { depth, divide } => {
+                let table = self
+                    .perft_table
+                    .get_or_insert_with(|| PerftTable::new(PERFT_HASH_MB));
+                let count = if divide {
+                    let counts = self.state.board.perft_divide(depth, Some(table));
+                    for (mov, count) in &counts {
+                        writeln!(self.output, "{}: {}", mov, count)?;
                     }
-
-                    if !ponder {
-                        let clock = match self.board.player() {
-                            PlayerV::White => wtime,
-                            PlayerV::Black => btime,
-                        };
-
-                        if let Some(clock) = clock {
-                            let max_wait = Duration::from_secs(10);
-                            // Naively assume there's 20 moves to go in the game
-                            std::thread::sleep((clock / 20).min(max_wait));
-                            self.stop()?;
+                    counts.iter().map(|(_, count)| count).sum()
+                } else {
+                    self.state.board.perft_hashed(depth, table)
+                };
+                writeln!(self.output, "{}", count)?;
+            }
+            PerftSuite { path, depth } => {
+                let table = self
+                    .perft_table
+                    .get_or_insert_with(|| PerftTable::new(PERFT_HASH_MB));
+                let mismatches = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|suite| perft_suite(&suite, depth, table));
+                match mismatches {
+                    Ok(mismatches) => {
+                        for mismatch in &mismatches {
+                            writeln!(self.output, "mismatch {}", mismatch)?;
                         }
+                        writeln!(self.output, "{} mismatches", mismatches.len())?;
                     }
-                }
-                Stop => {
-                    self.stop()?;
-                }
-                Perft(depth) => {
-                    let count = self.board.perft(depth);
-                    writeln!(self.output, "{}", count)?;
+                    Err(e) => eprintln!("{}", e),
                 }
             }
         }
 
-        Ok(())
+        Ok(true)
     }
 
     fn send(&mut self, message: &Message) -> Result<(), std::io::Error> {
         writeln!(self.output, "{}", message)
     }
 
-    fn go(&mut self) {
-        self.searcher.go(&self.board, None);
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), anyhow::Error> {
+        let value = || value.ok_or_else(|| anyhow!("Expected value for option {}", name));
+
+        // Option names are case-insensitive
+        match name.to_lowercase().as_str() {
+            // We always ponder when asked to, so there's nothing to change
+            "ponder" => {}
+            "hash" => {
+                self.hash_mb = value()?.parse::<usize>()?.clamp(1, MAX_HASH_MB);
+                self.rebuild_searcher();
+            }
+            "threads" => {
+                self.threads = value()?.parse::<u32>()?.clamp(1, MAX_THREADS);
+                self.rebuild_searcher();
+            }
+            "multipv" => {
+                self.multi_pv = value()?.parse::<u16>()?.clamp(1, MAX_MULTI_PV);
+                self.searcher.set_multi_pv(self.multi_pv);
+            }
+            "clear hash" => self.searcher.clear_hash(),
+            _ => return Err(anyhow!("Unrecognised option {}", name)),
+        }
+
+        Ok(())
+    }
+
+    fn rebuild_searcher(&mut self) {
+        self.searcher = Searcher::with_hash_size(self.threads, self.hash_mb);
+        self.searcher.set_multi_pv(self.multi_pv);
+    }
+
+    fn send_info(&mut self, info: SearchInfo) -> Result<(), std::io::Error> {
+        let mut infos = vec![
+            MultiPV(info.multipv),
+            Depth(info.depth),
+            SelDepth(info.seldepth),
+            Info::Score(info.score),
+        ];
+        match info.bound {
+            Bound::Exact => {}
+            Bound::Lower => infos.push(LowerBound),
+            Bound::Upper => infos.push(UpperBound),
+        }
+        infos.extend(vec![
+            Nodes(info.nodes),
+            Nps(info.nps()),
+            Time(info.time),
+            HashFull(info.hashfull),
+            PV(info.pv),
+        ]);
+        self.send(&Message::Info(infos))
     }
 
-    fn stop(&mut self) -> Result<(), std::io::Error> {
-        self.searcher.stop();
-        let pv = self.searcher.principal_variation(&mut self.board);
-        let mov = pv.first().copied();
-        let ponder = pv.get(1).copied();
+    /// Start searching in the background
+    fn go(&mut self, limits: SearchLimits) {
+        if self.status != Status::Idle {
+            eprintln!("Already searching, stopping the previous search");
+            self.searcher.stop();
+        }
+
+        self.status = if limits.is_limited() {
+            Status::Searching
+        } else {
+            Status::WaitingForStop
+        };
+        self.searcher.go(&self.state, limits);
+    }
 
-        self.send(&Message::Info(vec![PV(pv)]))?;
-        self.send(&BestMove { mov, ponder })?;
+    /// Report info from the search, and the best move if it has stopped on its own
+    fn poll_search(&mut self) -> Result<(), std::io::Error> {
+        let infos: Vec<SearchInfo> = self.searcher.info().collect();
+        for info in infos {
+            self.send_info(info)?;
+        }
 
-        self.ponder = ponder;
+        if self.status == Status::Searching && !self.searcher.is_searching() {
+            self.finish_search()?;
+        }
         Ok(())
     }
+
+    fn finish_search(&mut self) -> Result<(), std::io::Error> {
+        self.status = Status::Idle;
+
+        let infos: Vec<SearchInfo> = self.searcher.info().collect();
+        for info in infos {
+            self.send_info(info)?;
+        }
+
+        let result = self.searcher.result();
+        let mov = result.as_ref().and_then(SearchResult::best_move);
+        let ponder = result.as_ref().and_then(SearchResult::ponder_move);
+
+        self.send(&BestMove { mov, ponder })
+    }
 }
 
 enum Command {
@@ -141,13 +357,27 @@ enum Command {
     },
     PonderHit,
     Go {
-        movetime: Option<Duration>,
-        wtime: Option<Duration>,
-        btime: Option<Duration>,
+        /// Limits other than the clock, which depends on who is to move
+        limits: SearchLimits,
+        white: Option<Clock>,
+        black: Option<Clock>,
         ponder: bool,
     },
     Stop,
-    Perft(usize),
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    /// Count moves to a depth, optionally for each legal move
+    Perft {
+        depth: usize,
+        divide: bool,
+    },
+    /// Check the counts in an EPD perft suite file, up to a depth
+    PerftSuite {
+        path: String,
+        depth: usize,
+    },
 }
 
 impl FromStr for Command {
@@ -190,21 +420,47 @@ impl FromStr for Command {
             }
             "ponderhit" => PonderHit,
             "go" => {
-                let mut movetime = None;
-                let mut wtime = None;
-                let mut btime = None;
+                let mut limits = SearchLimits::default();
+                let mut white: Option<Clock> = None;
+                let mut black: Option<Clock> = None;
+                let mut moves_to_go = None;
                 let mut ponder = false;
 
                 while let Some(arg) = args.next() {
                     match arg {
                         "movetime" => {
-                            movetime.replace(read_duration(&mut args)?);
+                            limits.movetime.replace(read_duration(&mut args)?);
                         }
                         "wtime" => {
-                            wtime.replace(read_duration(&mut args)?);
+                            white.get_or_insert_with(Clock::default).time =
+                                read_duration(&mut args)?;
                         }
                         "btime" => {
-                            btime.replace(read_duration(&mut args)?);
+                            black.get_or_insert_with(Clock::default).time =
+                                read_duration(&mut args)?;
+                        }
+                        "winc" => {
+                            white.get_or_insert_with(Clock::default).increment =
+                                read_duration(&mut args)?;
+                        }
+                        "binc" => {
+                            black.get_or_insert_with(Clock::default).increment =
+                                read_duration(&mut args)?;
+                        }
+                        "movestogo" => {
+                            moves_to_go.replace(read_number(&mut args)?);
+                        }
+                        "depth" => {
+                            limits.depth.replace(read_number(&mut args)?);
+                        }
+                        "nodes" => {
+                            limits.nodes.replace(read_number(&mut args)?);
+                        }
+                        "mate" => {
+                            limits.mate.replace(read_number(&mut args)?);
+                        }
+                        "infinite" => {
+                            limits.infinite = true;
                         }
                         "ponder" => {
                             ponder = true;
@@ -213,15 +469,59 @@ impl FromStr for Command {
                     }
                 }
 
+                for clock in white.iter_mut().chain(black.iter_mut()) {
+                    clock.moves_to_go = moves_to_go;
+                }
+
                 Go {
-                    movetime,
-                    wtime,
-                    btime,
+                    limits,
+                    white,
+                    black,
                     ponder,
                 }
             }
             "stop" => Stop,
-            "perft" => Perft(arg("depth")?.parse::<usize>()?),
+            "setoption" => {
+                if arg("name")? != "name" {
+                    return Err(anyhow!("Expected name"));
+                }
+
+                // Names and values may contain spaces, e.g. "setoption name Clear Hash"
+                let name = args
+                    .by_ref()
+                    .take_while(|arg| *arg != "value")
+                    .collect::<Vec<&str>>()
+                    .join(" ");
+                let value = args.collect::<Vec<&str>>().join(" ");
+
+                SetOption {
+                    name,
+                    value: Some(value).filter(|value| !value.is_empty()),
+                }
+            }
+            "perft" => {
+                let perft = match arg("depth or suite")? {
+                    "suite" => PerftSuite {
+                        path: arg("path")?.to_string(),
+                        depth: match args.next() {
+                            Some(depth) => depth.parse::<usize>()?,
+                            None => usize::MAX,
+                        },
+                    },
+                    depth => {
+                        let depth = depth.parse::<usize>()?;
+                        let divide = args.next_if_eq(&"divide").is_some();
+                        if divide && depth == 0 {
+                            return Err(anyhow!("Expected a depth of at least 1 to divide"));
+                        }
+                        Perft { depth, divide }
+                    }
+                };
+                if let Some(unexpected) = args.next() {
+                    return Err(anyhow!("Unexpected argument {}", unexpected));
+                }
+                perft
+            }
             _ => return Err(anyhow!("Unrecognised command {}", command)),
         };
 
@@ -241,20 +541,24 @@ enum Message<'a> {
     Option {
         name: &'a str,
         typ: OptionType,
-        default: &'a str,
+        default: Option<&'a str>,
     },
 }
 
 impl<'a> Message<'a> {
     fn option(name: &'a str, typ: OptionType, default: &'a str) -> Self {
-        Message::Option { name, typ, default }
+        Message::Option {
+            name,
+            typ,
+            default: Some(default),
+        }
     }
 }
 
 impl<'a> fmt::Display for Message<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Message::ID(id, value) => write!(f, "{} {}", id, value)?,
+            Message::ID(id, value) => write!(f, "id {} {}", id, value)?,
             UciOk => write!(f, "uciok")?,
             ReadyOk => write!(f, "readyok")?,
             BestMove { mov, ponder } => {
@@ -268,12 +572,19 @@ impl<'a> fmt::Display for Message<'a> {
                 }
             }
             Message::Info(info) => {
+                write!(f, "info")?;
                 for i in info {
-                    write!(f, "{} ", i)?;
+                    write!(f, " {}", i)?;
                 }
             }
             Message::Option { name, typ, default } => {
-                write!(f, "option name {} type {} default {}", name, typ, default)?;
+                write!(f, "option name {} type {}", name, typ)?;
+                if let Some(default) = default {
+                    write!(f, " default {}", default)?;
+                }
+                if let Spin { min, max } = typ {
+                    write!(f, " min {} max {}", min, max)?;
+                }
             }
         }
         Ok(())
@@ -295,15 +606,37 @@ impl fmt::Display for ID {
 }
 
 enum Info {
+    MultiPV(u16),
+    Depth(u16),
+    SelDepth(u16),
+    Score(Score),
+    LowerBound,
+    UpperBound,
+    Nodes(u64),
+    Nps(u64),
+    Time(Duration),
+    HashFull(u16),
     PV(Vec<Move>),
 }
 
 impl fmt::Display for Info {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            MultiPV(multipv) => write!(f, "multipv {}", multipv)?,
+            Depth(depth) => write!(f, "depth {}", depth)?,
+            SelDepth(seldepth) => write!(f, "seldepth {}", seldepth)?,
+            Info::Score(Score::Centipawns(cp)) => write!(f, "score cp {}", cp)?,
+            Info::Score(Score::Mate(moves)) => write!(f, "score mate {}", moves)?,
+            LowerBound => write!(f, "lowerbound")?,
+            UpperBound => write!(f, "upperbound")?,
+            Nodes(nodes) => write!(f, "nodes {}", nodes)?,
+            Nps(nps) => write!(f, "nps {}", nps)?,
+            Time(time) => write!(f, "time {}", time.as_millis())?,
+            HashFull(hashfull) => write!(f, "hashfull {}", hashfull)?,
             PV(moves) => {
+                write!(f, "pv")?;
                 for mov in moves {
-                    write!(f, "{} ", mov)?;
+                    write!(f, " {}", mov)?;
                 }
             }
         }
@@ -313,35 +646,49 @@ impl fmt::Display for Info {
 
 enum OptionType {
     Check,
-    Spin,
-    Combo,
+    Spin { min: i64, max: i64 },
     Button,
-    String,
 }
 
 impl fmt::Display for OptionType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Check => write!(f, "check"),
-            Spin => write!(f, "spin"),
-            Combo => write!(f, "combo"),
+            Spin { .. } => write!(f, "spin"),
             Button => write!(f, "button"),
-            String => write!(f, "string"),
         }
     }
 }
 
 fn read_duration<'a>(args: &mut impl Iterator<Item = &'a str>) -> Result<Duration, anyhow::Error> {
+    // Some GUIs report negative time when a player has overstepped
+    let millis: i64 = read_number(args)?;
+    Ok(Duration::from_millis(millis.max(0) as u64))
+}
+
+fn read_number<'a, T>(args: &mut impl Iterator<Item = &'a str>) -> Result<T, anyhow::Error>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+{
     let value = args.next().ok_or_else(|| anyhow!("No argument value"))?;
-    Ok(Duration::from_millis(value.parse::<u64>()?))
+    Ok(value.parse::<T>()?)
 }
 
-fn run<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), std::io::Error> {
+fn run<R: BufRead + Send + 'static, W: Write>(
+    input: R,
+    output: &mut W,
+) -> Result<(), std::io::Error> {
     Uci {
         output,
-        board: Board::default(),
-        ponder: None,
-        searcher: Searcher::default(),
+        state: GameState::default(),
+        status: Status::Idle,
+        ponder_limits: None,
+        searcher: Searcher::with_hash_size(DEFAULT_THREADS, DEFAULT_HASH_MB),
+        perft_table: None,
+        hash_mb: DEFAULT_HASH_MB,
+        threads: DEFAULT_THREADS,
+        multi_pv: 1,
     }
     .run(input)
 }
@@ -387,13 +734,13 @@ mod tests {
     #[test]
     fn when_input_go_stop_then_return_a_valid_white_opening_move() {
         assert_that(&output_from(&["uci", "go", "stop"]))
-            .matching_contains(|out| white_openings().contains(&out.borrow()))
+            .matching_contains(|out| white_openings().contains(&without_ponder(out.borrow())))
     }
 
     #[test]
     fn when_input_position_moves_then_return_a_valid_move_from_that_position() {
         assert_that(&output_from(&["uci", "position moves a2a3", "go", "stop"]))
-            .matching_contains(|out| black_openings().contains(&out.borrow()))
+            .matching_contains(|out| black_openings().contains(&without_ponder(out.borrow())))
     }
 
     #[test]
@@ -405,7 +752,7 @@ mod tests {
             "go",
             "stop",
         ]))
-        .matching_contains(|out| white_openings().contains(&out))
+        .matching_contains(|out| white_openings().contains(&without_ponder(out)))
     }
 
     #[test]
@@ -422,7 +769,60 @@ mod tests {
             "go",
             "stop",
         ]))
-        .matching_contains(|out| valid_moves.contains(&out))
+        .matching_contains(|out| valid_moves.contains(&without_ponder(out)))
+    }
+
+    #[test]
+    fn when_input_isready_during_search_then_output_readyok_before_bestmove() {
+        let output = output_from(&["go infinite", "isready", "stop"]);
+        let readyok = output.iter().position(|out| out == "readyok");
+        let bestmove = output.iter().position(|out| out.starts_with("bestmove"));
+        assert_that(&readyok).is_some();
+        assert_that(&bestmove).is_some();
+        assert_that(&readyok).is_less_than(&bestmove);
+    }
+
+    #[test]
+    fn when_input_go_ponder_then_wait_for_ponderhit() {
+        let pondering = output_from(&["go ponder movetime 10"]);
+        assert_that(&pondering.iter().any(|out| out.starts_with("bestmove"))).is_false();
+        assert_that(&output_from(&["go ponder movetime 10", "ponderhit"]))
+            .matching_contains(|out| white_openings().contains(&without_ponder(out)));
+    }
+
+    #[test]
+    fn when_input_perft_then_output_the_count() {
+        assert_that(&output_from(&["position startpos moves e2e4", "perft 3"]))
+            .is_equal_to(vec!["13160".to_string()]);
+    }
+
+    #[test]
+    fn when_input_perft_divide_then_output_the_count_for_each_move() {
+        let output = output_from(&[
+            "position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+            "perft 2 divide",
+        ]);
+        assert_that(&output).has_length(16);
+        assert_that(&output).contains("e1g1: 3".to_string());
+        assert_that(&output.last()).is_equal_to(Some(&"66".to_string()));
+    }
+
+    #[test]
+    fn when_input_perft_again_then_output_the_same_count() {
+        let output = output_from(&["perft 3", "perft 3 divide", "perft 3"]);
+        assert_that(&output).has_length(23);
+        let totals: Vec<&String> = output.iter().filter(|out| *out == "8902").collect();
+        assert_that(&totals).has_length(3);
+    }
+
+    #[test]
+    fn when_input_perft_with_a_bad_argument_then_output_nothing() {
+        assert_that(&output_from(&[
+            "perft 0 divide",
+            "perft 3 foo",
+            "perft 3 divide foo",
+        ]))
+        .is_empty();
     }
 
     fn white_openings() -> Vec<String> {
@@ -447,10 +847,15 @@ mod tests {
             .collect()
     }
 
+    /// "bestmove e2e4 e7e5" becomes "bestmove e2e4"
+    fn without_ponder(out: &str) -> String {
+        out.split_whitespace().take(2).collect::<Vec<_>>().join(" ")
+    }
+
     fn output_from(input: &[&str]) -> Vec<String> {
         let input_strs: Vec<String> = input.into_iter().map(|x| x.to_string()).collect();
         let input_str = input_strs.join("\n");
-        let reader = BufReader::new(input_str.as_bytes());
+        let reader = BufReader::new(std::io::Cursor::new(input_str.into_bytes()));
         let mut output: Vec<u8> = Vec::new();
         run(reader, &mut output).unwrap();
         let output_str = String::from_utf8_lossy(&output);
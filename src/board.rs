@@ -1,9 +1,11 @@
 use crate::move_generation::PieceType;
 use crate::piece::Piece;
 use crate::{
-    bitboards, moves::PlayedMove, piece, typed_player, Bitboard, Black, File, King, Move,
-    PieceTypeV, PieceTypeV::Pawn, PieceV, Player, PlayerV, Rank, Rook, Square, SquareColor,
-    SquareMap, White,
+    eval,
+    moves::{PlayedMove, PlayedNullMove},
+    piece, typed_player, Bitboard, Black, File, Move, PieceTypeV,
+    PieceTypeV::Pawn,
+    PieceV, Player, PlayerV, Rank, Rook, Square, SquareColor, SquareMap, White,
 };
 use anyhow::Error;
 use enum_map::EnumMap;
@@ -11,11 +13,15 @@ use piece::{BB, BK, BN, BP, BQ, BR, WB, WK, WN, WP, WQ, WR};
 use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Formatter;
+use std::hash::{Hash, Hasher};
 use std::ops::Index;
 use std::str::FromStr;
+use zobrist::ZOBRIST;
+
+mod zobrist;
 
 /// Represents a game in-progress
-#[derive(Eq, PartialEq, Clone, Hash)]
+#[derive(Clone)]
 pub struct Board {
     /// The player whose turn it is
     player: PlayerV,
@@ -27,6 +33,14 @@ pub struct Board {
     player_boards: EnumMap<PlayerV, Bitboard>,
     /// Castling rights
     flags: BoardFlags,
+    /// Zobrist hash of the position, updated incrementally as moves are made
+    hash: u64,
+    /// Zobrist hash of just the pawns
+    pawn_hash: u64,
+    /// Number of half-moves since the last capture or pawn move, for the fifty-move rule
+    halfmove_clock: u16,
+    /// Starts at 1 and is incremented after black moves
+    fullmove_number: u16,
 }
 
 impl Board {
@@ -82,11 +96,15 @@ impl Board {
     ) -> Self {
         let mut piece_boards = EnumMap::<PieceTypeV, Bitboard>::new();
         let mut player_boards = EnumMap::<PlayerV, Bitboard>::new();
+        let mut hash = ZOBRIST.player(player.value()) ^ ZOBRIST.flags(flags);
+        let mut pawn_hash = 0;
 
         for (square, piece) in pieces.iter() {
             if let Some(piece) = piece {
                 piece_boards[piece.piece_type].set(square);
                 player_boards[piece.player].set(square);
+                hash ^= ZOBRIST.piece(*piece, square);
+                pawn_hash ^= ZOBRIST.pawn(*piece, square);
             }
         }
 
@@ -96,6 +114,10 @@ impl Board {
             piece_boards,
             player_boards,
             flags,
+            hash,
+            pawn_hash,
+            halfmove_clock: 0,
+            fullmove_number: 1,
         }
     }
 
@@ -104,6 +126,34 @@ impl Board {
         self.player
     }
 
+    /// [Zobrist hash](https://www.chessprogramming.org/Zobrist_Hashing) of the position.
+    ///
+    /// Two boards with the same pieces, player, castling rights and en-passant file always have
+    /// the same hash.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Hash of the pawn structure, ignoring all other pieces
+    pub fn pawn_hash(&self) -> u64 {
+        self.pawn_hash
+    }
+
+    /// Number of half-moves since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    /// Number of the current move, starting at 1 and incremented after black moves
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
+    pub(crate) fn set_move_numbers(&mut self, halfmove_clock: u16, fullmove_number: u16) {
+        self.halfmove_clock = halfmove_clock;
+        self.fullmove_number = fullmove_number;
+    }
+
     /// Perform a move on the board, mutating the board
     pub fn make_move(&mut self, mov: Move) -> PlayedMove {
         typed_player!(self.player, |p| self.make_move_for(mov, p))
@@ -126,6 +176,11 @@ impl Board {
         debug_assert_eq!(player.value(), self.player);
 
         let prev_flags = self.flags;
+        let prev_hash = self.hash;
+        let prev_pawn_hash = self.pawn_hash;
+        let prev_halfmove_clock = self.halfmove_clock;
+
+        let mut hash = self.hash ^ ZOBRIST.flags(prev_flags) ^ ZOBRIST.player(player.value());
 
         let from = mov.from();
         let to = mov.to();
@@ -139,6 +194,8 @@ impl Board {
             let cap_type = captured_piece.piece_type;
             self.piece_boards[cap_type].reset(to);
             self.player_boards[player.opponent().value()].reset(to);
+            hash ^= ZOBRIST.piece(captured_piece, to);
+            self.pawn_hash ^= ZOBRIST.pawn(captured_piece, to);
             (Some(cap_type), false)
         } else if self.en_passant_square() == Some(to) && piece.piece_type == PieceTypeV::Pawn {
             let cap_square = to.shift_rank(self.player.opponent().multiplier());
@@ -148,6 +205,8 @@ impl Board {
                     self.piece_boards[cap_type].reset(cap_square);
                     self.player_boards[player.opponent().value()].reset(cap_square);
                     self.pieces[cap_square] = None;
+                    hash ^= ZOBRIST.piece(captured_piece, cap_square);
+                    self.pawn_hash ^= ZOBRIST.pawn(captured_piece, cap_square);
                     (Some(cap_type), true)
                 }
                 None => (None, false),
@@ -157,15 +216,20 @@ impl Board {
         };
 
         self.pieces[from] = None;
+        hash ^= ZOBRIST.piece(piece, from);
+        self.pawn_hash ^= ZOBRIST.pawn(piece, from);
 
         if let Some(promotion_type) = mov.promoting() {
             let promotion = Piece::newv(player, promotion_type);
             self.piece_boards[piece.piece_type].reset(from);
             self.piece_boards[promotion_type].set(to);
             self.pieces[to] = Some(promotion);
+            hash ^= ZOBRIST.piece(promotion, to);
         } else {
             self.piece_boards[piece.piece_type].move_bit(from, to);
             self.pieces[to] = Some(piece);
+            hash ^= ZOBRIST.piece(piece, to);
+            self.pawn_hash ^= ZOBRIST.pawn(piece, to);
         }
 
         self.player_boards[player.value()].move_bit(from, to);
@@ -196,6 +260,7 @@ impl Board {
                 self.pieces[rook_to] = Some(rook);
                 self.piece_boards[PieceTypeV::Rook].move_bit(rook_from, rook_to);
                 self.player_boards[player.value()].move_bit(rook_from, rook_to);
+                hash ^= ZOBRIST.piece(rook, rook_from) ^ ZOBRIST.piece(rook, rook_to);
             }
         }
 
@@ -207,12 +272,18 @@ impl Board {
             };
         self.flags.set_en_passant_file(en_passant_file);
 
-        // Set in-check flag
-        let new_attacks = piece
-            .piece_type
-            .attacks(to, self.occupancy(), self.player, self.flags);
-        let enemy_king = self.bitboard_piece(Piece::new(self.player.opponent(), King));
-        if new_attacks & enemy_king != bitboards::EMPTY {
+        if piece.piece_type == PieceTypeV::Pawn || captured_piece_type.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+
+        if player.value() == PlayerV::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+
+        // Set in-check flag, including discovered checks
+        if self.in_check(player.opponent()) {
             self.flags.set(BoardFlags::CHECK);
         } else {
             self.flags.unset(BoardFlags::CHECK);
@@ -220,7 +291,49 @@ impl Board {
 
         self.player = self.player.opponent();
 
-        PlayedMove::new(mov, captured_piece_type, en_passant_capture, prev_flags)
+        hash ^= ZOBRIST.flags(self.flags) ^ ZOBRIST.player(self.player);
+        self.hash = hash;
+
+        PlayedMove::new(
+            mov,
+            captured_piece_type,
+            en_passant_capture,
+            prev_flags,
+            prev_hash,
+            prev_pawn_hash,
+            prev_halfmove_clock,
+        )
+    }
+
+    /// Pass the turn to the opponent without moving. Not legal in chess, but useful to the search
+    /// to find out how strong a position is.
+    ///
+    /// Must not be called when the player to move is in check.
+    pub fn make_null_move(&mut self) -> PlayedNullMove {
+        debug_assert!(!self.flags.in_check());
+
+        let pmov = PlayedNullMove {
+            flags: self.flags,
+            hash: self.hash,
+            halfmove_clock: self.halfmove_clock,
+        };
+
+        let mut hash = self.hash ^ ZOBRIST.flags(self.flags) ^ ZOBRIST.player(self.player);
+        self.flags.set_en_passant_file(None);
+        self.player = self.player.opponent();
+        hash ^= ZOBRIST.flags(self.flags) ^ ZOBRIST.player(self.player);
+        self.hash = hash;
+        // Positions either side of a null move aren't really repetitions
+        self.halfmove_clock = 0;
+
+        pmov
+    }
+
+    pub fn unmake_null_move(&mut self, pmov: PlayedNullMove) {
+        self.flags = pmov.flags;
+        self.hash = pmov.hash;
+        self.halfmove_clock = pmov.halfmove_clock;
+        self.player = self.player.opponent();
     }
 
     /// Perform a move on the board, mutating the board
@@ -236,6 +349,9 @@ impl Board {
             capture,
             en_passant_capture,
             flags,
+            hash,
+            pawn_hash,
+            halfmove_clock,
         } = pmov;
 
         let from = mov.from();
@@ -250,7 +366,13 @@ impl Board {
         };
 
         self.flags = flags;
+        self.hash = hash;
+        self.pawn_hash = pawn_hash;
+        self.halfmove_clock = halfmove_clock;
         self.player = player.value();
+        if player.value() == PlayerV::Black {
+            self.fullmove_number = self.fullmove_number.saturating_sub(1);
+        }
 
         if let Some(promotion_type) = mov.promoting() {
             self.piece_boards[piece.piece_type].set(from);
@@ -356,20 +478,9 @@ impl Board {
         self.flags.en_passant_square(self.player)
     }
 
+    /// Static evaluation from the point of view of the player to move, in centipawns
     pub fn eval(&self) -> i32 {
-        let white_centric_score = 200 * (self.ecount(WK) - self.ecount(BK))
-            + 9 * (self.ecount(WQ) - self.ecount(BQ))
-            + 5 * (self.ecount(WR) - self.ecount(BR))
-            + 3 * (self.ecount(WB) - self.ecount(BB))
-            + 3 * (self.ecount(WN) - self.ecount(BN))
-            + (self.ecount(WP) - self.ecount(BP));
-
-        white_centric_score * self.player.multiplier() as i32 * 100
-        // TODO: mobility, isolated pawns, blah blah blah
-    }
-
-    fn ecount(&self, piece: Piece<impl Player, impl PieceType>) -> i32 {
-        self.count(piece) as i32
+        eval::evaluate(self)
     }
 
     pub fn count(&self, piece: Piece<impl Player, impl PieceType>) -> u8 {
@@ -437,6 +548,24 @@ impl Default for Board {
     }
 }
 
+/// Boards are equal if they represent the same position, regardless of move counters.
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+            && self.player == other.player
+            && self.flags == other.flags
+            && self.pieces == other.pieces
+    }
+}
+
+impl Eq for Board {}
+
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
 impl fmt::Debug for Board {
     fn fmt<'a>(&self, f: &mut fmt::Formatter<'a>) -> fmt::Result {
         let fen = self.to_fen();
@@ -530,6 +659,11 @@ impl BoardFlags {
         BoardFlags(x)
     }
 
+    /// Whether the player to move is in check
+    pub fn in_check(self) -> bool {
+        self.is_set(Self::CHECK)
+    }
+
     pub fn is_set(self, mask: u16) -> bool {
         self.0 & mask != 0
     }
@@ -557,6 +691,11 @@ impl BoardFlags {
         }
     }
 
+    /// The four castling bits, `0bKQkq`
+    pub fn castling_bits(self) -> u16 {
+        (self.0 >> 4) & 0b1111
+    }
+
     pub fn set_en_passant_file(&mut self, file: Option<File>) {
         if let Some(file) = file {
             self.set(Self::EN_PASSANT);
@@ -786,10 +925,100 @@ pub mod tests {
         assert_eq!(board, expected_board);
     }
 
+    #[test]
+    fn halfmove_clock_counts_moves_since_last_capture_or_pawn_move() {
+        let mut board = fen("4k3/8/8/8/3p4/8/4P3/4K1N1 w - -");
+        assert_eq!(board.halfmove_clock(), 0);
+
+        board.make_move(mov!(g1f3));
+        assert_eq!(board.halfmove_clock(), 1);
+        let pmov = board.make_move(mov!(e8d7));
+        assert_eq!(board.halfmove_clock(), 2);
+
+        board.unmake_move(pmov);
+        assert_eq!(board.halfmove_clock(), 1);
+
+        board.make_move(mov!(d4d3));
+        assert_eq!(board.halfmove_clock(), 0);
+        board.make_move(mov!(f3e5));
+        assert_eq!(board.halfmove_clock(), 1);
+        board.make_move(mov!(d3e2));
+        assert_eq!(board.halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn fullmove_number_is_incremented_after_black_moves() {
+        let mut board = Board::default();
+        assert_eq!(board.fullmove_number(), 1);
+
+        board.make_move(mov!(e2e4));
+        assert_eq!(board.fullmove_number(), 1);
+        let pmov = board.make_move(mov!(e7e5));
+        assert_eq!(board.fullmove_number(), 2);
+
+        board.unmake_move(pmov);
+        assert_eq!(board.fullmove_number(), 1);
+    }
+
+    #[test]
+    fn transposed_positions_have_the_same_hash() {
+        let mut board1 = Board::default();
+        for mov in &[mov!(g1f3), mov!(g8f6), mov!(b1c3)] {
+            board1.make_move(*mov);
+        }
+
+        let mut board2 = Board::default();
+        for mov in &[mov!(b1c3), mov!(g8f6), mov!(g1f3)] {
+            board2.make_move(*mov);
+        }
+
+        assert_eq!(board1.hash(), board2.hash());
+    }
+
+    #[test]
+    fn pawn_hash_only_depends_on_pawns() {
+        let board = fen("r3k2r/8/8/8/3p4/8/4P3/R3K2R w KQkq -");
+        let other_pieces = fen("4k3/8/8/8/3p4/8/4P3/4KQ2 b - -");
+        let other_pawns = fen("r3k2r/8/8/8/3p4/4P3/8/R3K2R w KQkq -");
+
+        assert_eq!(board.pawn_hash(), other_pieces.pawn_hash());
+        assert_ne!(board.pawn_hash(), other_pawns.pawn_hash());
+    }
+
+    #[test]
+    fn hash_depends_on_player_castling_and_en_passant() {
+        let board = fen("r3k2r/8/8/8/3p4/8/4P3/R3K2R w KQkq -");
+        let other_player = fen("r3k2r/8/8/8/3p4/8/4P3/R3K2R b KQkq -");
+        let no_castling = fen("r3k2r/8/8/8/3p4/8/4P3/R3K2R w - -");
+        let mut en_passant = fen("r3k2r/8/8/8/3p4/8/4P3/R3K2R w KQkq -");
+        en_passant.make_move(mov!(e2e4));
+        let no_en_passant = fen("r3k2r/8/8/8/3pP3/8/8/R3K2R b KQkq -");
+
+        assert_ne!(board.hash(), other_player.hash());
+        assert_ne!(board.hash(), no_castling.hash());
+        assert_ne!(en_passant.hash(), no_en_passant.hash());
+    }
+
     pub fn fen(fen: &str) -> Board {
         Board::from_fen(fen).unwrap()
     }
 
+    #[test]
+    fn null_move_passes_the_turn() {
+        let mut board = fen("4k3/8/8/8/4P3/8/8/4K3 b - e3");
+        let before = board.clone();
+        assert!(board.en_passant_square().is_some());
+
+        let pmov = board.make_null_move();
+        assert_eq!(board.player(), PlayerV::White);
+        assert_eq!(board.en_passant_square(), None);
+        assert_eq!(board.hash(), fen("4k3/8/8/8/4P3/8/8/4K3 w").hash());
+
+        board.unmake_null_move(pmov);
+        assert_eq!(board, before);
+        assert_eq!(board.hash(), before.hash());
+    }
+
     proptest! {
         #[test]
         fn legal_moves_can_be_unmade((board_before, mov) in board_and_move(arb_board())) {
@@ -861,6 +1090,18 @@ pub mod tests {
             }
         }
 
+        let expected = Board::with_states(board.pieces.clone(), board.player, board.flags);
+        assert_eq!(
+            board.hash, expected.hash,
+            "Expected hash to be {:#018x}, but was {:#018x}",
+            expected.hash, board.hash
+        );
+        assert_eq!(
+            board.pawn_hash, expected.pawn_hash,
+            "Expected pawn hash to be {:#018x}, but was {:#018x}",
+            expected.pawn_hash, board.pawn_hash
+        );
+
         let in_check = board.in_check(board.player);
         let flag = board.flags.is_set(BoardFlags::CHECK);
         assert_eq!(
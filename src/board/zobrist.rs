@@ -0,0 +1,76 @@
+use crate::{BoardFlags, PieceTypeV, PieceV, PlayerV, Square, SquareMap};
+use enum_map::EnumMap;
+use lazy_static::lazy_static;
+
+/// Random numbers used to build a [Zobrist hash](https://www.chessprogramming.org/Zobrist_Hashing)
+/// of a board.
+///
+/// The hash is the XOR of one number for every feature of the position, so making a move only
+/// needs to XOR out the features that changed and XOR in the new ones.
+pub struct ZobristKeys {
+    pieces: EnumMap<PlayerV, EnumMap<PieceTypeV, SquareMap<u64>>>,
+    black_to_move: u64,
+    /// Indexed by the four castling bits of [BoardFlags](crate::BoardFlags)
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+}
+
+impl ZobristKeys {
+    pub fn piece(&self, piece: PieceV, square: Square) -> u64 {
+        self.pieces[piece.player][piece.piece_type][square]
+    }
+
+    /// Same as [piece](Self::piece) for pawns, 0 for anything else. Used to hash the pawn
+    /// structure by itself.
+    pub fn pawn(&self, piece: PieceV, square: Square) -> u64 {
+        if piece.piece_type == PieceTypeV::Pawn {
+            self.piece(piece, square)
+        } else {
+            0
+        }
+    }
+
+    pub fn player(&self, player: PlayerV) -> u64 {
+        match player {
+            PlayerV::White => 0,
+            PlayerV::Black => self.black_to_move,
+        }
+    }
+
+    /// Castling rights and en-passant file. The check flag is ignored because it's derived from
+    /// the rest of the position.
+    pub fn flags(&self, flags: BoardFlags) -> u64 {
+        let castling = self.castling[flags.castling_bits() as usize];
+        match flags.en_passant_file() {
+            Some(file) => castling ^ self.en_passant[file.to_index() as usize],
+            None => castling,
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref ZOBRIST: ZobristKeys = {
+        // Fixed seed so hashes are stable between runs, which we need for books and debugging
+        let mut state = 0x5EED_5EED_5EED_5EEDu64;
+        let mut next = move || {
+            // splitmix64
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+
+        let pieces = EnumMap::from(|_| EnumMap::from(|_| SquareMap::from(|_| next())));
+        let black_to_move = next();
+        let castling = array_init::array_init(|_| next());
+        let en_passant = array_init::array_init(|_| next());
+
+        ZobristKeys {
+            pieces,
+            black_to_move,
+            castling,
+            en_passant,
+        }
+    };
+}
@@ -0,0 +1,241 @@
+use crate::{Board, PieceTypeV, PieceV, Player, PlayerV};
+use pawns::PawnTable;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+mod king_safety;
+mod mobility;
+mod pawns;
+mod pst;
+
+/// Game phase when all pieces are on the board. Each piece contributes towards this, so the phase
+/// drops as pieces are traded off until it reaches 0 in a pawn endgame.
+const MAX_PHASE: i32 = 24;
+
+/// Evaluate the position from the point of view of the player to move, in centipawns.
+///
+/// Every term has a middlegame and endgame value, which are blended based on how much material
+/// is left on the board.
+pub fn evaluate(board: &Board) -> i32 {
+    evaluate_with_pawns(board, white_minus_black(board, pawns::evaluate))
+}
+
+/// Evaluates positions, caching results between calls where it can
+#[derive(Default)]
+pub struct Evaluator {
+    pawns: PawnTable,
+}
+
+impl Evaluator {
+    /// Same as [evaluate]
+    pub fn evaluate(&mut self, board: &Board) -> i32 {
+        let pawn_structure = self.pawns.evaluate(board);
+        evaluate_with_pawns(board, pawn_structure)
+    }
+}
+
+fn evaluate_with_pawns(board: &Board, pawn_structure: Tapered) -> i32 {
+    let score = material_and_position(board)
+        + pawn_structure
+        + white_minus_black(board, mobility::evaluate)
+        + white_minus_black(board, king_safety::evaluate);
+    let white_centric_score = score.interpolate(phase(board));
+    white_centric_score * board.player().multiplier() as i32
+}
+
+/// Score of a term from white's point of view, given how to score it for either player
+fn white_minus_black(board: &Board, term: fn(&Board, PlayerV) -> Tapered) -> Tapered {
+    term(board, PlayerV::White) - term(board, PlayerV::Black)
+}
+
+/// Value of all pieces on their squares, from white's point of view
+fn material_and_position(board: &Board) -> Tapered {
+    let mut score = Tapered::default();
+
+    for (piece_type, pieces) in board.piece_boards() {
+        for (player, player_pieces) in board.player_boards() {
+            let piece = PieceV::newv(player, piece_type);
+            for square in (*pieces & *player_pieces).squares() {
+                match player {
+                    PlayerV::White => score += pst::piece_square(piece, square),
+                    PlayerV::Black => score -= pst::piece_square(piece, square),
+                }
+            }
+        }
+    }
+
+    score
+}
+
+/// How far from the endgame we are, from [MAX_PHASE] at the start of the game to 0
+fn phase(board: &Board) -> i32 {
+    let phase: i32 = board
+        .piece_boards()
+        .iter()
+        .map(|(piece_type, pieces)| phase_weight(piece_type) * pieces.count() as i32)
+        .sum();
+    // Promotions could take us past the starting phase
+    phase.min(MAX_PHASE)
+}
+
+fn phase_weight(piece_type: PieceTypeV) -> i32 {
+    match piece_type {
+        PieceTypeV::King | PieceTypeV::Pawn => 0,
+        PieceTypeV::Knight | PieceTypeV::Bishop => 1,
+        PieceTypeV::Rook => 2,
+        PieceTypeV::Queen => 4,
+    }
+}
+
+/// A score with separate middlegame and endgame values
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Tapered {
+    mg: i32,
+    eg: i32,
+}
+
+impl Tapered {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    /// Blend the middlegame and endgame values, given the [phase] of the game
+    pub fn interpolate(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Tapered {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl AddAssign for Tapered {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Tapered {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+impl SubAssign for Tapered {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Tapered {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Tapered {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self {
+        Self::new(self.mg * rhs, self.eg * rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tests::fen;
+    use crate::strategies::*;
+    use crate::BoardFlags;
+    use pretty_assertions::assert_eq;
+    use proptest::proptest;
+
+    /// Score of a term for white in the given position
+    pub(super) fn white(term: fn(&Board, PlayerV) -> Tapered, fen_str: &str) -> Tapered {
+        term(&fen(fen_str), PlayerV::White)
+    }
+
+    #[test]
+    fn starting_position_is_equal() {
+        assert_eq!(evaluate(&Board::default()), 0);
+    }
+
+    #[test]
+    fn starting_position_is_middlegame() {
+        assert_eq!(phase(&Board::default()), MAX_PHASE);
+    }
+
+    #[test]
+    fn pawn_endgame_is_endgame() {
+        assert_eq!(phase(&fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w")), 0);
+    }
+
+    #[test]
+    fn eval_is_from_point_of_view_of_player_to_move() {
+        let white = fen("4k3/8/8/8/8/8/8/3QK3 w");
+        let black = fen("4k3/8/8/8/8/8/8/3QK3 b");
+        assert!(evaluate(&white) > 0);
+        assert_eq!(evaluate(&white), -evaluate(&black));
+    }
+
+    #[test]
+    fn knight_is_better_in_centre_than_on_rim() {
+        let centre = fen("4k3/8/8/8/3N4/8/8/4K3 w");
+        let rim = fen("4k3/8/8/8/N7/8/8/4K3 w");
+        assert!(evaluate(&centre) > evaluate(&rim));
+    }
+
+    #[test]
+    fn king_prefers_shelter_in_middlegame_and_centre_in_endgame() {
+        let sheltered = fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w");
+        let exposed = fen("rnbqkbnr/pppppppp/8/8/4K3/8/PPPPPPPP/RNBQ1R2 w");
+        assert!(evaluate(&sheltered) > evaluate(&exposed));
+
+        let sheltered = fen("4k3/pppp4/8/8/8/8/4PPPP/6K1 w");
+        let central = fen("4k3/pppp4/8/8/4K3/8/4PPPP/8 w");
+        assert!(evaluate(&central) > evaluate(&sheltered));
+    }
+
+    #[test]
+    fn advanced_pawns_are_worth_more_in_endgame() {
+        let advanced = fen("4k3/8/3P4/8/8/8/8/4K3 w");
+        let home = fen("4k3/8/8/8/8/8/3P4/4K3 w");
+        assert!(evaluate(&advanced) > evaluate(&home));
+    }
+
+    #[test]
+    fn evaluator_gives_the_same_result_as_evaluate() {
+        let mut evaluator = Evaluator::default();
+        for fen_str in &[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w",
+            "4k3/pp3p2/8/2P5/3P4/8/8/4K3 b",
+            "r3k2r/8/8/8/3p4/8/4P3/R3K2R w",
+        ] {
+            let board = fen(fen_str);
+            assert_eq!(evaluator.evaluate(&board), evaluate(&board));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn eval_is_the_same_for_mirrored_position(pieces in arb_pieces(), player in arb_player()) {
+            let mut mirrored: [[Option<PieceV>; 8]; 8] = Default::default();
+            for (rank, row) in pieces.iter().enumerate() {
+                mirrored[7 - rank] = row.map(|piece| {
+                    piece.map(|piece| PieceV::newv(piece.player.opponent(), piece.piece_type))
+                });
+            }
+
+            let board = Board::new(pieces, player, BoardFlags::default());
+            let mirrored = Board::new(mirrored, player.opponent(), BoardFlags::default());
+            assert_eq!(evaluate(&board), evaluate(&mirrored));
+        }
+    }
+}
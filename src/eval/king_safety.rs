@@ -0,0 +1,140 @@
+use crate::eval::{pawns, Tapered};
+use crate::move_generation::PieceType;
+use crate::{bitboards, Bitboard, Board, King, PieceTypeV, Player, PlayerV};
+
+/// Penalty per unit of attack on the king zone, see [ATTACK_WEIGHT]
+const KING_ZONE_ATTACK: Tapered = Tapered::new(-6, -1);
+/// Bonus for each pawn directly in front of the king, or one square further
+const PAWN_SHIELD: [Tapered; 2] = [Tapered::new(14, 0), Tapered::new(7, 0)];
+/// Penalty for a file next to or under the king without own pawns, depending on whether the
+/// opponent still has a pawn there
+const OPEN_FILE: Tapered = Tapered::new(-22, 0);
+const HALF_OPEN_FILE: Tapered = Tapered::new(-10, 0);
+
+/// How dangerous each attacked king zone square is, for knights, bishops, rooks and queens
+const ATTACK_WEIGHT: [(PieceTypeV, i32); 4] = [
+    (PieceTypeV::Knight, 2),
+    (PieceTypeV::Bishop, 2),
+    (PieceTypeV::Rook, 3),
+    (PieceTypeV::Queen, 5),
+];
+
+/// Percentage of the attack weight that counts, by number of attacking pieces. A lone attacker
+/// can rarely achieve anything, but a coordinated attack is very dangerous.
+const ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+/// How safe one player's king is from attack
+pub fn evaluate(board: &Board, player: PlayerV) -> Tapered {
+    let kings = board.piece_boards()[PieceTypeV::King] & board.player_boards()[player];
+    let mut score = Tapered::default();
+    for king in kings.squares() {
+        let king = Bitboard::from(king);
+        let zone = king | King.attacks(king.first_set(), board.occupancy(), player, board.flags());
+        score += attackers(board, player, zone) + shelter(board, player, king);
+    }
+    score
+}
+
+/// Penalty for enemy pieces attacking the squares around the king
+fn attackers(board: &Board, player: PlayerV, zone: Bitboard) -> Tapered {
+    let opp = board.player_boards()[player.opponent()];
+    let mut attackers = 0;
+    let mut weight = 0;
+
+    for (piece_type, piece_weight) in ATTACK_WEIGHT.iter() {
+        let pieces = board.piece_boards()[*piece_type] & opp;
+        for source in pieces.squares() {
+            let attacks =
+                piece_type.attacks(source, board.occupancy(), player.opponent(), board.flags());
+            let attacked = (attacks & zone).count() as i32;
+            if attacked > 0 {
+                attackers += 1;
+                weight += piece_weight * attacked;
+            }
+        }
+    }
+
+    let scale = ATTACKER_SCALE[attackers.min(ATTACKER_SCALE.len() - 1)];
+    KING_ZONE_ATTACK * (weight * scale / 100)
+}
+
+/// Pawns in front of the king, and open files that give enemy rooks a way in
+fn shelter(board: &Board, player: PlayerV, king: Bitboard) -> Tapered {
+    let pawns = board.piece_boards()[PieceTypeV::Pawn];
+    let own = pawns & board.player_boards()[player];
+    let opp = pawns & board.player_boards()[player.opponent()];
+    let mut score = Tapered::default();
+
+    let files = king | pawns::adjacent_files(king);
+    let mut shield = player.advance_bitboard(files);
+    for bonus in PAWN_SHIELD.iter() {
+        score += *bonus * (shield & own).count() as i32;
+        shield = player.advance_bitboard(shield);
+    }
+
+    for file in files.squares() {
+        let file = bitboards::FILES[file.file()];
+        if own & file == bitboards::EMPTY {
+            if opp & file == bitboards::EMPTY {
+                score += OPEN_FILE;
+            } else {
+                score += HALF_OPEN_FILE;
+            }
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tests::fen;
+    use crate::eval::tests::white;
+    use crate::eval::white_minus_black;
+    use crate::{Square, White};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn full_pawn_shield_is_rewarded() {
+        let castled = white(evaluate, "4k3/pppppppp/8/8/8/8/5PPP/6K1 w");
+        assert_eq!(castled, PAWN_SHIELD[0] * 3);
+    }
+
+    #[test]
+    fn advanced_shield_pawns_are_worth_less() {
+        let advanced = white(evaluate, "4k3/pppppppp/8/8/8/6P1/5P1P/6K1 w");
+        assert_eq!(advanced, PAWN_SHIELD[0] * 2 + PAWN_SHIELD[1]);
+    }
+
+    #[test]
+    fn open_files_near_the_king_are_penalised() {
+        let half_open = white(evaluate, "4k3/pppppppp/8/8/8/8/5P1P/6K1 w");
+        assert_eq!(half_open, PAWN_SHIELD[0] * 2 + HALF_OPEN_FILE);
+
+        let open = white(evaluate, "4k3/pppppp1p/8/8/8/8/5P1P/6K1 w");
+        assert_eq!(open, PAWN_SHIELD[0] * 2 + OPEN_FILE);
+    }
+
+    #[test]
+    fn a_single_attacker_is_ignored() {
+        let board = fen("4k3/8/8/8/8/8/5PPP/3r2K1 w");
+        let zone = Bitboard::from(Square::G1)
+            | King.attacks(Square::G1, board.occupancy(), White, board.flags());
+        assert_eq!(attackers(&board, PlayerV::White, zone), Tapered::default());
+    }
+
+    #[test]
+    fn coordinated_attack_on_the_king_is_penalised() {
+        let attacked = white(evaluate, "4k3/8/8/7q/8/5n2/5PPP/6K1 w");
+        let defended = white(evaluate, "4k3/8/8/q7/8/n7/5PPP/6K1 w");
+        assert!(attacked.mg < defended.mg);
+    }
+
+    #[test]
+    fn kings_without_shelter_are_less_safe() {
+        let exposed = white_minus_black(&fen("r3k3/pppppppp/8/8/4K3/8/PPPPPPPP/8 w"), evaluate);
+        let castled = white_minus_black(&fen("r3k3/pppppppp/8/8/8/8/PPPPPPPP/6K1 w"), evaluate);
+        assert!(castled.mg > exposed.mg);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::eval::{pawns, Tapered};
+use crate::move_generation::PieceType;
+use crate::{Board, PieceTypeV, Player, PlayerV};
+
+/// Bonus for each square a piece can safely move to, for knights, bishops, rooks and queens
+const MOBILITY: [Tapered; 4] = [
+    Tapered::new(4, 4),
+    Tapered::new(5, 5),
+    Tapered::new(2, 4),
+    Tapered::new(1, 2),
+];
+
+/// Typical number of safe squares for each piece type, so an average piece scores nothing
+const AVERAGE_MOBILITY: [i32; 4] = [4, 6, 7, 13];
+
+const MOBILE_PIECES: [PieceTypeV; 4] = [
+    PieceTypeV::Knight,
+    PieceTypeV::Bishop,
+    PieceTypeV::Rook,
+    PieceTypeV::Queen,
+];
+
+/// How freely one player's pieces can move
+pub fn evaluate(board: &Board, player: PlayerV) -> Tapered {
+    let own = board.player_boards()[player];
+    let opp_pawns =
+        board.piece_boards()[PieceTypeV::Pawn] & board.player_boards()[player.opponent()];
+    // Squares guarded by enemy pawns aren't really available, since the piece would be lost
+    let safe = !(own | pawns::attacks(player.opponent(), opp_pawns));
+
+    let mut score = Tapered::default();
+
+    for (index, piece_type) in MOBILE_PIECES.iter().enumerate() {
+        let pieces = board.piece_boards()[*piece_type] & own;
+        for source in pieces.squares() {
+            let attacks = piece_type.attacks(source, board.occupancy(), player, board.flags());
+            let mobility = (attacks & safe).count() as i32;
+            score += MOBILITY[index] * (mobility - AVERAGE_MOBILITY[index]);
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::tests::white;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn knight_mobility_counts_safe_squares() {
+        // 8 squares from d4, minus the one occupied by the king
+        assert_eq!(white(evaluate, "4k3/8/8/8/3N4/8/4K3/8 w"), MOBILITY[0] * 3);
+        // c6 and e6 are guarded by the pawn on d7
+        assert_eq!(white(evaluate, "4k3/3p4/8/8/3N4/8/4K3/8 w"), MOBILITY[0]);
+    }
+
+    #[test]
+    fn blocked_bishop_is_penalised() {
+        assert_eq!(
+            white(evaluate, "4k3/8/8/8/8/1P1P4/2B5/4K3 w"),
+            MOBILITY[1] * -4
+        );
+        assert_eq!(white(evaluate, "4k3/8/8/8/8/8/2B5/4K3 w"), MOBILITY[1] * 3);
+    }
+
+    #[test]
+    fn rook_on_open_file_is_more_mobile() {
+        assert_eq!(
+            white(evaluate, "4k3/8/8/8/8/8/1PP5/R3K3 w"),
+            MOBILITY[2] * 3
+        );
+        assert_eq!(
+            white(evaluate, "4k3/8/8/8/8/8/PPP5/R3K3 w"),
+            MOBILITY[2] * -4
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use crate::eval::Tapered;
+use crate::{bitboards, Bitboard, Board, File, PieceTypeV, Player, PlayerV};
+
+const DOUBLED: Tapered = Tapered::new(-10, -25);
+const ISOLATED: Tapered = Tapered::new(-12, -15);
+const BACKWARD: Tapered = Tapered::new(-8, -10);
+const CONNECTED: Tapered = Tapered::new(8, 6);
+/// Bonus for a passed pawn, indexed by its rank from its owner's point of view
+const PASSED: [Tapered; 8] = [
+    Tapered::new(0, 0),
+    Tapered::new(5, 10),
+    Tapered::new(8, 15),
+    Tapered::new(12, 25),
+    Tapered::new(20, 45),
+    Tapered::new(35, 80),
+    Tapered::new(60, 130),
+    Tapered::new(0, 0),
+];
+
+/// Number of entries in a [PawnTable] by default. Each entry is 24 bytes.
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+/// Cache of pawn structure scores, keyed by the [pawn hash](Board::pawn_hash).
+///
+/// Pawns move rarely compared to other pieces, so most positions in a search share a pawn
+/// structure with one we've already seen.
+pub struct PawnTable {
+    entries: Vec<Option<(u64, Tapered)>>,
+}
+
+impl PawnTable {
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: vec![None; size.max(1)],
+        }
+    }
+
+    /// Score of the pawn structure from white's point of view
+    pub fn evaluate(&mut self, board: &Board) -> Tapered {
+        let key = board.pawn_hash();
+        let index = key as usize % self.entries.len();
+        match self.entries[index] {
+            Some((entry_key, score)) if entry_key == key => score,
+            _ => {
+                let score = super::white_minus_black(board, evaluate);
+                self.entries[index] = Some((key, score));
+                score
+            }
+        }
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new(PAWN_TABLE_SIZE)
+    }
+}
+
+/// Penalties for weak pawns and bonuses for strong ones, for one player
+pub fn evaluate(board: &Board, player: PlayerV) -> Tapered {
+    let pawns = board.piece_boards()[PieceTypeV::Pawn];
+    let own = pawns & board.player_boards()[player];
+    let opp = pawns & board.player_boards()[player.opponent()];
+    let own_attacks = attacks(player, own);
+    let opp_attacks = attacks(player.opponent(), opp);
+
+    let mut score = Tapered::default();
+
+    for file in File::VALUES.iter() {
+        let on_file = (own & bitboards::FILES[file]).count() as i32;
+        if on_file > 1 {
+            score += DOUBLED * (on_file - 1);
+        }
+    }
+
+    for square in own.squares() {
+        let pawn = Bitboard::from(square);
+        let front = front_span(player, pawn);
+
+        if own & adjacent_files(bitboards::FILES[square.file()]) == bitboards::EMPTY {
+            score += ISOLATED;
+        } else {
+            // Can't be defended by other pawns, because they've all advanced past it
+            let rear = fill(player.opponent(), pawn);
+            let unsupported = own & adjacent_files(rear) == bitboards::EMPTY;
+            let stop_attacked = player.advance_bitboard(pawn) & opp_attacks != bitboards::EMPTY;
+            if unsupported && stop_attacked {
+                score += BACKWARD;
+            }
+        }
+
+        let defended = own_attacks & pawn != bitboards::EMPTY;
+        let phalanx = own & adjacent_files(pawn) != bitboards::EMPTY;
+        if defended || phalanx {
+            score += CONNECTED;
+        }
+
+        let blockers = opp & (front | adjacent_files(front));
+        let behind_own_pawn = own & front != bitboards::EMPTY;
+        if blockers == bitboards::EMPTY && !behind_own_pawn {
+            let rank = match player {
+                PlayerV::White => square.rank().to_index(),
+                PlayerV::Black => 7 - square.rank().to_index(),
+            };
+            score += PASSED[rank as usize];
+        }
+    }
+
+    score
+}
+
+/// Squares attacked by the given pawns
+pub fn attacks(player: impl Player, pawns: Bitboard) -> Bitboard {
+    adjacent_files(player.advance_bitboard(pawns))
+}
+
+pub fn adjacent_files(bitboard: Bitboard) -> Bitboard {
+    bitboard.shift_file(1) | bitboard.shift_file(-1)
+}
+
+/// The given squares and all squares in front of them, from the player's point of view
+fn fill(player: impl Player, mut bitboard: Bitboard) -> Bitboard {
+    for _ in 0..7 {
+        bitboard |= player.advance_bitboard(bitboard);
+    }
+    bitboard
+}
+
+/// All squares in front of the given squares, from the player's point of view
+fn front_span(player: impl Player, bitboard: Bitboard) -> Bitboard {
+    fill(player, player.advance_bitboard(bitboard))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tests::fen;
+    use crate::eval::tests::white;
+    use crate::eval::white_minus_black;
+    use crate::strategies::*;
+    use pretty_assertions::assert_eq;
+    use proptest::proptest;
+
+    #[test]
+    fn doubled_pawns_are_penalised() {
+        assert_eq!(
+            white(evaluate, "4k3/1p6/8/8/8/P7/P7/4K3 w"),
+            DOUBLED + ISOLATED * 2
+        );
+    }
+
+    #[test]
+    fn isolated_pawn_is_penalised() {
+        assert_eq!(white(evaluate, "4k3/4p3/8/8/8/8/3P4/4K3 w"), ISOLATED);
+    }
+
+    #[test]
+    fn backward_pawn_is_penalised() {
+        // d2 can't advance safely and c3 is already past it
+        let backward = white(evaluate, "4k3/8/8/3p4/4p3/2P5/3P4/4K3 w");
+        let supported = white(evaluate, "4k3/8/8/3p4/4p3/8/2PP4/4K3 w");
+        assert_eq!(backward, CONNECTED + BACKWARD);
+        assert_eq!(supported, CONNECTED * 2);
+    }
+
+    #[test]
+    fn connected_pawns_get_a_bonus() {
+        assert_eq!(white(evaluate, "4k3/3p4/8/8/8/8/2PP4/4K3 w"), CONNECTED * 2);
+        assert_eq!(white(evaluate, "4k3/3p4/8/8/8/3P4/2P5/4K3 w"), CONNECTED);
+    }
+
+    #[test]
+    fn passed_pawn_bonus_increases_with_rank() {
+        let rank_4 = white(evaluate, "4k3/8/8/8/3P4/8/8/4K3 w") - ISOLATED;
+        let rank_6 = white(evaluate, "4k3/8/3P4/8/8/8/8/4K3 w") - ISOLATED;
+        assert_eq!(rank_4, PASSED[3]);
+        assert_eq!(rank_6, PASSED[5]);
+
+        let black_rank_4 = evaluate(&fen("4k3/8/8/3p4/8/8/8/4K3 w"), PlayerV::Black);
+        assert_eq!(black_rank_4 - ISOLATED, PASSED[3]);
+    }
+
+    #[test]
+    fn pawn_blocked_by_adjacent_file_is_not_passed() {
+        assert_eq!(white(evaluate, "4k3/2p5/8/8/3P4/8/8/4K3 w"), ISOLATED);
+    }
+
+    #[test]
+    fn pawn_table_returns_the_same_score() {
+        let board = fen("4k3/pp3p2/8/2P5/3P4/8/8/4K3 w");
+        let mut table = PawnTable::new(16);
+        assert_eq!(table.evaluate(&board), white_minus_black(&board, evaluate));
+        assert_eq!(table.evaluate(&board), white_minus_black(&board, evaluate));
+    }
+
+    proptest! {
+        #[test]
+        fn pawn_table_matches_direct_evaluation(board in arb_board()) {
+            let mut table = PawnTable::new(1);
+            assert_eq!(table.evaluate(&board), white_minus_black(&board, evaluate));
+        }
+    }
+}
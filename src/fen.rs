@@ -51,6 +51,7 @@ impl Board {
         let mut flags = BoardFlags::default();
 
         if let Some(castling) = fields.next() {
+            flags.unset(White.castle_flags() | Black.castle_flags());
             if castling.contains('K') {
                 flags.set(White.castle_kingside_flag());
             }
@@ -63,9 +64,6 @@ impl Board {
             if castling.contains('q') {
                 flags.set(Black.castle_queenside_flag());
             }
-            if castling == "-" {
-                flags.unset(White.castle_flags() | Black.castle_flags());
-            }
         }
 
         if let Some(ep) = fields.next().filter(|e| e != &"-") {
@@ -73,9 +71,23 @@ impl Board {
             flags.set_en_passant_file(Some(ep_square.file()));
         }
 
-        // TODO: also parse number of moves
+        let halfmove_clock = match fields.next() {
+            Some(halfmove) => halfmove
+                .parse::<u16>()
+                .with_context(|| anyhow!("Invalid halfmove clock: {}", halfmove))?,
+            None => 0,
+        };
+
+        let fullmove_number = match fields.next() {
+            Some(fullmove) => fullmove
+                .parse::<u16>()
+                .with_context(|| anyhow!("Invalid fullmove number: {}", fullmove))?,
+            None => 1,
+        };
 
-        Ok(Self::new(pieces_array, player, flags))
+        let mut board = Self::new(pieces_array, player, flags);
+        board.set_move_numbers(halfmove_clock, fullmove_number);
+        Ok(board)
     }
 
     pub fn to_fen(&self) -> String {
@@ -147,6 +159,12 @@ impl Board {
             fen.push('-');
         }
 
+        fen.push(' ');
+        fen.push_str(&self.halfmove_clock().to_string());
+
+        fen.push(' ');
+        fen.push_str(&self.fullmove_number().to_string());
+
         fen
     }
 }
@@ -227,4 +245,41 @@ mod tests {
         let board = Board::from_fen(fen).unwrap();
         assert_eq!(board, Board::default());
     }
+
+    #[test]
+    fn can_write_board_to_fen_notation() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(Board::default().to_fen(), fen);
+    }
+
+    #[test]
+    fn fen_round_trips_move_numbers_castling_and_en_passant() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 12 40",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/8/8/8/8/8/K6k b - - 99 120",
+        ];
+
+        for fen in &fens {
+            assert_eq!(&Board::from_fen(*fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn missing_move_numbers_default_to_start_of_game() {
+        let board = Board::from_fen("8/8/8/8/8/8/8/K6k w - -").unwrap();
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 1);
+    }
+
+    #[test]
+    fn move_numbers_are_updated_by_moves() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K1N1 w - - 7 30").unwrap();
+        board.make_move("g1f3".parse().unwrap());
+        board.make_move("e8d7".parse().unwrap());
+        assert_eq!(board.to_fen(), "8/3k4/8/8/8/5N2/4P3/4K3 w - - 9 31");
+        board.make_move("e2e4".parse().unwrap());
+        assert_eq!(board.to_fen(), "8/3k4/8/8/4P3/5N2/8/4K3 b - e3 0 31");
+    }
 }
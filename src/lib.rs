@@ -3,6 +3,7 @@
 
 mod bitboard;
 mod board;
+mod eval;
 mod fen;
 mod file;
 mod move_generation;
@@ -22,12 +23,17 @@ pub use crate::{
     bitboard::{bitboards, Bitboard},
     board::{Board, BoardFlags},
     file::File,
-    move_generation::{magic, Bishop, King, Knight, Pawn, PieceTypeV, Queen, Rook},
-    moves::{Move, PlayedMove},
+    move_generation::{
+        magic, Bishop, King, Knight, MoveKind, MoveList, Pawn, PieceTypeV, Queen, Rook,
+    },
+    moves::{Move, PackedMove, PlayedMove},
+    perft::{perft_suite, PerftMismatch, PerftPosition, PerftTable},
     piece::{Piece, PieceV},
     player::{Black, Player, PlayerV, White},
     rank::{Rank, RankMap},
-    search::Searcher,
+    search::{
+        Bound, Clock, Score, SearchCallbacks, SearchInfo, SearchLimits, SearchResult, Searcher,
+    },
     square::{Square, SquareColor, SquareMap},
 };
 
@@ -60,4 +66,150 @@ impl GameState {
     pub fn moves(&self) -> impl Iterator<Item = &Move> {
         self.moves.iter().map(|pm| &pm.mov)
     }
+
+    /// Hashes of every position before the current one, from the start of the game
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = u64> + '_ {
+        self.moves.iter().map(|pm| pm.hash)
+    }
+
+    /// True if the current position has appeared at least twice before.
+    ///
+    /// Only positions since the last capture or pawn move are checked, because those moves can
+    /// never be undone.
+    pub fn is_threefold_repetition(&self) -> bool {
+        let hash = self.board.hash();
+        let repetitions = self
+            .history()
+            .rev()
+            .take(self.board.halfmove_clock() as usize)
+            .filter(|h| *h == hash)
+            .count();
+        repetitions >= 2
+    }
+
+    /// True if there have been fifty moves by each player without a capture or pawn move
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.board.halfmove_clock() >= 100
+    }
+
+    /// Result of the game, or `None` if the game is still in progress
+    pub fn outcome(&self) -> Option<Outcome> {
+        let player = self.board.player();
+        let no_legal_moves = self.board.moves().next().is_none();
+
+        // Checkmate takes priority over the fifty-move rule
+        if no_legal_moves {
+            if self.board.in_check(player) {
+                Some(Outcome::Checkmate {
+                    winner: player.opponent(),
+                })
+            } else {
+                Some(Outcome::Stalemate)
+            }
+        } else if self.is_threefold_repetition() {
+            Some(Outcome::ThreefoldRepetition)
+        } else if self.is_fifty_move_draw() {
+            Some(Outcome::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Checkmate { winner: PlayerV },
+    Stalemate,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tests::fen;
+    use pretty_assertions::assert_eq;
+
+    fn play(state: &mut GameState, moves: &[&str]) {
+        for mov in moves {
+            state.push_move(mov.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn position_repeated_three_times_is_threefold_repetition() {
+        let mut state = GameState::default();
+        play(&mut state, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert!(!state.is_threefold_repetition());
+
+        play(&mut state, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert!(state.is_threefold_repetition());
+        assert_eq!(state.outcome(), Some(Outcome::ThreefoldRepetition));
+    }
+
+    #[test]
+    fn repetitions_before_a_pawn_move_are_not_counted() {
+        let mut state = GameState::default();
+        play(&mut state, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        play(&mut state, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        play(&mut state, &["e2e3"]);
+        assert!(!state.is_threefold_repetition());
+    }
+
+    #[test]
+    fn popping_a_move_undoes_repetition() {
+        let mut state = GameState::default();
+        play(&mut state, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        play(&mut state, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        state.pop();
+        assert!(!state.is_threefold_repetition());
+    }
+
+    #[test]
+    fn outcome_is_checkmate_when_player_has_no_moves_in_check() {
+        let mut state = GameState::default();
+        play(&mut state, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(
+            state.outcome(),
+            Some(Outcome::Checkmate {
+                winner: PlayerV::Black
+            })
+        );
+    }
+
+    #[test]
+    fn outcome_is_stalemate_when_player_has_no_moves_out_of_check() {
+        let state = GameState::new(fen("k7/8/1Q6/8/8/8/8/7K b - -"));
+        assert_eq!(state.outcome(), Some(Outcome::Stalemate));
+    }
+
+    #[test]
+    fn fifty_moves_without_capture_or_pawn_move_is_a_draw() {
+        let mut state = GameState::new(fen("8/8/8/4k3/8/8/8/R3K3 w - - 99 80"));
+        assert!(!state.is_fifty_move_draw());
+
+        play(&mut state, &["a1a2"]);
+        assert!(state.is_fifty_move_draw());
+        assert_eq!(state.outcome(), Some(Outcome::FiftyMoveRule));
+    }
+
+    #[test]
+    fn checkmate_takes_priority_over_fifty_move_rule() {
+        let mut state = GameState::new(fen("7k/8/6K1/8/8/8/8/R7 w - - 99 80"));
+        play(&mut state, &["a1a8"]);
+        assert_eq!(
+            state.outcome(),
+            Some(Outcome::Checkmate {
+                winner: PlayerV::White
+            })
+        );
+    }
+
+    #[test]
+    fn outcome_is_none_during_game() {
+        let mut state = GameState::default();
+        play(&mut state, &["e2e4", "e7e5"]);
+        assert_eq!(state.outcome(), None);
+    }
 }
@@ -3,28 +3,71 @@ use std::iter::Chain;
 pub use crate::move_generation::bishop::Bishop;
 pub use crate::move_generation::king::King;
 pub use crate::move_generation::knight::Knight;
+pub use crate::move_generation::move_list::MoveList;
 pub use crate::move_generation::pawn::Pawn;
 pub use crate::move_generation::piece_type::PieceType;
 pub use crate::move_generation::piece_type::PieceTypeV;
 pub use crate::move_generation::queen::Queen;
 pub use crate::move_generation::rook::Rook;
 pub use crate::piece::Piece;
-use crate::{bitboards, Bitboard, Board, BoardFlags, File, Move, PlayedMove, Player, Square};
+use crate::{
+    bitboards, Bitboard, Black, Board, BoardFlags, File, Move, PlayedMove, Player, PlayerV, Square,
+    White,
+};
+pub(crate) use legality::Legality;
+use magic::Magic;
+use piece_type::MovesIter;
 
 mod bishop;
 mod king;
 mod knight;
+mod legality;
 pub mod magic;
+mod move_list;
 mod pawn;
 mod piece_type;
 mod queen;
 mod rook;
 
+/// Which moves [Board::generate] adds to a [MoveList]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MoveKind {
+    /// See [Board::moves]
+    Legal,
+    /// See [Board::pseudo_legal_moves]
+    PseudoLegal,
+    /// See [Board::capturing_moves]
+    Captures,
+    /// See [Board::promoting_moves]
+    Promotions,
+    /// See [Board::quiet_moves]
+    Quiets,
+    /// See [Board::checking_moves]
+    Checks,
+    /// See [Board::evasions]
+    Evasions,
+}
+
 impl Board {
+    /// Add moves of the given kind for the player to move to the list
+    pub fn generate(&self, kind: MoveKind, moves: &mut MoveList) {
+        let player = self.player();
+        match kind {
+            MoveKind::Legal => moves.extend(self.moves()),
+            MoveKind::PseudoLegal => moves.extend(self.pseudo_legal_moves()),
+            MoveKind::Captures => moves.extend(self.capturing_moves(player)),
+            MoveKind::Promotions => moves.extend(self.promoting_moves(player)),
+            MoveKind::Quiets => moves.extend(self.quiet_moves(player)),
+            MoveKind::Checks => moves.extend(self.checking_moves(player)),
+            MoveKind::Evasions => moves.extend(self.evasions(player)),
+        }
+    }
+
     /// Lazy iterator of all legal moves
-    pub fn moves(&mut self) -> impl Iterator<Item = Move> + '_ {
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        let legality = Legality::new(self);
         self.pseudo_legal_moves()
-            .filter(move |mov| self.check_legal(*mov))
+            .filter(move |mov| legality.is_legal(self, *mov))
     }
 
     /// See [pseudo_legal_moves_for]
@@ -44,51 +87,84 @@ impl Board {
         self.moves_of_type(CapturingMoves(player))
     }
 
-    fn moves_of_type(&self, movement: impl Movement) -> impl Iterator<Item = Move> {
-        movement.moves(self)
+    /// Lazy iterator of pseudo-legal moves that don't capture or promote
+    pub fn quiet_moves(&self, player: impl Player) -> impl Iterator<Item = Move> {
+        self.moves_of_type(QuietMoves(player))
     }
 
-    pub fn check_legal(&mut self, mov: Move) -> bool {
-        match self.make_if_legal(mov) {
-            None => false,
-            Some(pmov) => {
-                self.unmake_move(pmov);
-                true
-            }
-        }
+    /// Lazy iterator of pseudo-legal promotions that don't capture. Capturing promotions are in
+    /// [capturing_moves].
+    pub fn promoting_moves(&self, player: impl Player) -> impl Iterator<Item = Move> {
+        self.moves_of_type(PromotingMoves(player))
     }
 
-    pub fn make_if_legal(&mut self, mov: Move) -> Option<PlayedMove> {
-        let me = self.player();
+    /// Lazy iterator of pseudo-legal moves that don't capture or promote but give check
+    pub fn checking_moves(&self, player: impl Player) -> impl Iterator<Item = Move> {
+        self.moves_of_type(CheckingMoves::new(self, player))
+    }
 
-        let my_king = Piece::new(me, King);
-        let piece = self[mov.from()].unwrap();
+    /// Lazy iterator of pseudo-legal moves that might get the player out of check. When not in
+    /// check, this is every pseudo-legal move.
+    pub fn evasions(&self, player: impl Player) -> impl Iterator<Item = Move> {
+        self.moves_of_type(EvadingMoves::new(self, player))
+    }
 
-        let castling = piece == my_king.value() && (mov.from().file() - mov.to().file()).abs() == 2;
-        if castling {
-            let through = if mov.to().file() == File::KINGSIDE {
-                me.castle_kingside_through()
-            } else {
-                me.castle_queenside_through()
-            };
-            let attacks = self.attacks(me.opponent());
+    fn moves_of_type(&self, movement: impl Movement) -> impl Iterator<Item = Move> {
+        movement.moves(self)
+    }
 
-            if through & attacks != bitboards::EMPTY {
-                return None;
-            }
+    /// Whether a move is one of [pseudo_legal_moves], without generating them all. Useful for
+    /// moves that were found in another position, like the best move from the transposition table.
+    pub fn is_pseudo_legal(&self, mov: Move) -> bool {
+        let player = self.player();
+        let (from, to) = (mov.from(), mov.to());
+        let piece = match self[from] {
+            Some(piece) if piece.player == player => piece,
+            _ => return false,
+        };
+
+        let pawn = piece.piece_type == PieceTypeV::Pawn;
+        let promotes = pawn && to.rank() == player.opponent().back_rank();
+        let valid_promotion = match mov.promoting() {
+            None => !promotes,
+            Some(PieceTypeV::King | PieceTypeV::Pawn) => false,
+            Some(_) => promotes,
+        };
+        if !valid_promotion {
+            return false;
         }
 
-        let pmov = self.make_move(mov);
-        let king_board = self.bitboard_piece(my_king);
-        // TODO: this `attacks` check is the slowest part of move generation
-        let attacks = self.attacks(me.opponent());
-        let in_check = king_board & attacks != bitboards::EMPTY;
-        if in_check {
-            // TODO: can we avoid actually making+unmaking the move in this case?
-            self.unmake_move(pmov);
-            None
+        let occupancy = self.occupancy();
+        let flags = self.flags();
+        let targets = if pawn {
+            let mut captures = self.occupancy_player(player.opponent());
+            if let Some(en_passant) = flags.en_passant_square(player) {
+                captures.set(en_passant);
+            }
+            (Pawn.attacks(from, occupancy, player, flags) & captures)
+                | Pawn.other_moves(from, occupancy, player, flags)
         } else {
-            Some(pmov)
+            piece.piece_type.movement(from, occupancy, player, flags)
+        };
+
+        (targets & !self.occupancy_player(player)).get(to)
+    }
+
+    /// Whether a pseudo-legal move doesn't leave the player's king in check.
+    ///
+    /// This works out the pins and checks from scratch, so when checking many moves in the same
+    /// position build a [Legality] once instead.
+    pub fn check_legal(&self, mov: Move) -> bool {
+        Legality::new(self).is_legal(self, mov)
+    }
+
+    /// Make a pseudo-legal move if it doesn't leave the player's king in check, see
+    /// [check_legal](Self::check_legal)
+    pub fn make_if_legal(&mut self, mov: Move) -> Option<PlayedMove> {
+        if self.check_legal(mov) {
+            Some(self.make_move(mov))
+        } else {
+            None
         }
     }
 
@@ -99,9 +175,9 @@ impl Board {
             .any(|pos| self.is_square_attacked(king_player.opponent(), pos))
     }
 
-    pub fn checkmate(&mut self) -> bool {
+    pub fn checkmate(&self) -> bool {
         let in_check = self.in_check(self.player());
-        let mut no_legal_moves = || self.moves().next().is_none();
+        let no_legal_moves = || self.moves().next().is_none();
         in_check && no_legal_moves()
     }
 
@@ -116,13 +192,113 @@ impl Board {
         let bishops = self.bitboard_piece(Piece::new(attacker, Bishop));
         let knights = self.bitboard_piece(Piece::new(attacker, Knight));
         let pawns = self.bitboard_piece(Piece::new(attacker, Pawn));
+        let kings = self.bitboard_piece(Piece::new(attacker, King));
         let rook_attacks = Rook.attacks(sq, occ, other, self.flags()) & (rooks | queens);
         let bishop_attacks = Bishop.attacks(sq, occ, other, self.flags()) & (bishops | queens);
         let knight_attacks = Knight.attacks(sq, occ, other, self.flags()) & knights;
         let pawn_attacks = Pawn.attacks(sq, occ, other, self.flags()) & pawns;
-        (rook_attacks | bishop_attacks | knight_attacks | pawn_attacks) != bitboards::EMPTY
+        let king_attacks = King.attacks(sq, occ, other, self.flags()) & kings;
+        (rook_attacks | bishop_attacks | knight_attacks | pawn_attacks | king_attacks)
+            != bitboards::EMPTY
+    }
+
+    /// All pieces of either player attacking the square, given the occupancy. Pieces behind a
+    /// slider are included once the slider is removed from the occupancy.
+    fn attackers_to(&self, sq: Square, occ: Bitboard) -> Bitboard {
+        let flags = self.flags();
+        let pieces = self.piece_boards();
+        let players = self.player_boards();
+        let rooks = pieces[PieceTypeV::Rook] | pieces[PieceTypeV::Queen];
+        let bishops = pieces[PieceTypeV::Bishop] | pieces[PieceTypeV::Queen];
+        let white_pawns = Pawn.attacks(sq, occ, Black, flags) & players[PlayerV::White];
+        let black_pawns = Pawn.attacks(sq, occ, White, flags) & players[PlayerV::Black];
+
+        (Rook.attacks(sq, occ, White, flags) & rooks)
+            | (Bishop.attacks(sq, occ, White, flags) & bishops)
+            | (Knight.attacks(sq, occ, White, flags) & pieces[PieceTypeV::Knight])
+            | (King.attacks(sq, occ, White, flags) & pieces[PieceTypeV::King])
+            | ((white_pawns | black_pawns) & pieces[PieceTypeV::Pawn])
     }
 
+    /// [Static exchange evaluation](https://www.chessprogramming.org/Static_Exchange_Evaluation)
+    /// of a move: how much material the player wins if both sides keep recapturing on the target
+    /// square with their least valuable piece, stopping whenever it's better not to.
+    ///
+    /// Negative if the move loses material, e.g. capturing a defended pawn with a queen.
+    pub fn see(&self, mov: Move) -> i32 {
+        let to = mov.to();
+        let attacker = match self[mov.from()] {
+            Some(attacker) => attacker,
+            None => return 0,
+        };
+
+        let mut occupancy = self.occupancy();
+        occupancy.reset(mov.from());
+
+        let victim = match self[to] {
+            Some(victim) => Some(victim.piece_type),
+            None if attacker.piece_type == PieceTypeV::Pawn && mov.from().file() != to.file() => {
+                // En passant, the captured pawn isn't on the target square
+                occupancy.reset(Square::new(to.file(), mov.from().rank()));
+                Some(PieceTypeV::Pawn)
+            }
+            None => None,
+        };
+
+        // gain[n] is the material won by the nth capture, assuming the exchange continues
+        let mut gain = [0; 32];
+        gain[0] = victim.map_or(0, see_value);
+        let mut on_square = attacker.piece_type;
+        if let Some(promoting) = mov.promoting() {
+            gain[0] += see_value(promoting) - see_value(PieceTypeV::Pawn);
+            on_square = promoting;
+        }
+
+        let mut player = attacker.player.opponent();
+        let mut depth = 0;
+
+        while depth + 1 < gain.len() {
+            let attackers = self.attackers_to(to, occupancy) & occupancy;
+            let own_attackers = attackers & self.player_boards()[player];
+            let least_valuable = SEE_ORDER.iter().find_map(|piece_type| {
+                let pieces = own_attackers & self.piece_boards()[*piece_type];
+                if pieces == bitboards::EMPTY {
+                    None
+                } else {
+                    Some((*piece_type, pieces.first_set()))
+                }
+            });
+
+            let (piece_type, from) = match least_valuable {
+                Some(least_valuable) => least_valuable,
+                None => break,
+            };
+
+            // The king can't capture onto a defended square
+            let defenders = attackers & self.player_boards()[player.opponent()];
+            if piece_type == PieceTypeV::King && defenders != bitboards::EMPTY {
+                break;
+            }
+
+            depth += 1;
+            gain[depth] = see_value(on_square) - gain[depth - 1];
+            occupancy.reset(from);
+            on_square = piece_type;
+            player = player.opponent();
+        }
+
+        // Either side can choose to stop recapturing if it would lose material
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+
+        gain[0]
+    }
+
+    /// Every square attacked by the player. Legality checks only need the attackers of a few
+    /// squares, so this is only used to test them.
+    #[cfg(test)]
     fn attacks(&self, player: impl Player) -> Bitboard {
         let king = self.attacks_for_piece(Piece::new(player, King));
         let queen = self.attacks_for_piece(Piece::new(player, Queen));
@@ -133,6 +309,7 @@ impl Board {
         king | queen | rook | bishop | knight | pawn
     }
 
+    #[cfg(test)]
     fn attacks_for_piece(&self, piece: Piece<impl Player, impl PieceType>) -> Bitboard {
         let pt = &piece.piece_type;
         let mut attacks = bitboards::EMPTY;
@@ -143,6 +320,28 @@ impl Board {
     }
 }
 
+/// Pieces in the order they should be used to recapture
+const SEE_ORDER: [PieceTypeV; 6] = [
+    PieceTypeV::Pawn,
+    PieceTypeV::Knight,
+    PieceTypeV::Bishop,
+    PieceTypeV::Rook,
+    PieceTypeV::Queen,
+    PieceTypeV::King,
+];
+
+/// Rough material values used to evaluate exchanges, in centipawns
+pub fn see_value(piece_type: PieceTypeV) -> i32 {
+    match piece_type {
+        PieceTypeV::Pawn => 100,
+        PieceTypeV::Knight => 320,
+        PieceTypeV::Bishop => 330,
+        PieceTypeV::Rook => 500,
+        PieceTypeV::Queen => 900,
+        PieceTypeV::King => 20_000,
+    }
+}
+
 pub trait Movement: Copy {
     type Moves: Iterator<Item = Move>;
 
@@ -241,8 +440,304 @@ impl<P: Player> Movement for CapturingMoves<P> {
     }
 }
 
+/// Moves that don't capture or promote
+#[derive(Copy, Clone)]
+pub struct QuietMoves<P>(P);
+
+impl<P: Player> Movement for QuietMoves<P> {
+    type Moves = Chain<PieceMoves<P, Self>, pawn::Pushes<P>>;
+
+    fn movement(
+        &self,
+        piece_type: &impl PieceType,
+        source: Square,
+        occupancy: Bitboard,
+        flags: BoardFlags,
+    ) -> Bitboard {
+        piece_type.movement(source, occupancy, self.0, flags) & !occupancy
+    }
+
+    fn moves(&self, board: &Board) -> Self::Moves {
+        let mask = !board.occupancy();
+        piece_moves(self.0, board, *self, mask, mask).chain(pawn::quiet_moves(self.0, board, mask))
+    }
+}
+
+/// Promotions that don't capture, which only pawns can make
+#[derive(Copy, Clone)]
+pub struct PromotingMoves<P>(P);
+
+impl<P: Player> Movement for PromotingMoves<P> {
+    type Moves = pawn::Moves<P>;
+
+    fn movement(
+        &self,
+        piece_type: &impl PieceType,
+        source: Square,
+        occupancy: Bitboard,
+        flags: BoardFlags,
+    ) -> Bitboard {
+        if piece_type.value() != PieceTypeV::Pawn {
+            return bitboards::EMPTY;
+        }
+        let last_rank = bitboards::RANKS[self.0.opponent().back_rank()];
+        piece_type.other_moves(source, occupancy, self.0, flags) & last_rank
+    }
+
+    fn moves(&self, board: &Board) -> Self::Moves {
+        pawn::promoting_moves(self.0, board)
+    }
+}
+
+/// Moves that don't capture or promote but give check, either by attacking the opponent's king or
+/// by getting out of the way of a slider that then does
+#[derive(Copy, Clone)]
+pub struct CheckingMoves<P> {
+    player: P,
+    /// The opponent's king, if they have one
+    king: Option<Square>,
+    /// The player's sliders with exactly one of the player's own pieces between them and the king
+    snipers: Bitboard,
+    /// The pieces in the way of the snipers
+    discoverers: Bitboard,
+    /// The player's rooks and queens
+    straight: Bitboard,
+    /// The player's bishops and queens
+    diagonal: Bitboard,
+}
+
+impl<P: Player> CheckingMoves<P> {
+    fn new(board: &Board, player: P) -> Self {
+        let king = board
+            .bitboard_piece(Piece::new(player.opponent(), King))
+            .squares()
+            .next();
+        let us = board.occupancy_player(player.value());
+        let them = board.occupancy_player(player.opponent().value());
+        let pieces = board.piece_boards();
+        let queens = pieces[PieceTypeV::Queen];
+        let straight = (pieces[PieceTypeV::Rook] | queens) & us;
+        let diagonal = (pieces[PieceTypeV::Bishop] | queens) & us;
+
+        let mut snipers = bitboards::EMPTY;
+        let mut discoverers = bitboards::EMPTY;
+        if let Some(king) = king {
+            // Only the opponent's pieces block these rays, so they reach past the player's pieces
+            let candidates = (Rook.magic_moves(king, them) & straight)
+                | (Bishop.magic_moves(king, them) & diagonal);
+            for sniper in candidates.squares() {
+                let blockers = legality::between(king, sniper) & board.occupancy();
+                if blockers.count() == 1 && blockers & them == bitboards::EMPTY {
+                    snipers.set(sniper);
+                    discoverers |= blockers;
+                }
+            }
+        }
+
+        Self {
+            player,
+            king,
+            snipers,
+            discoverers,
+            straight,
+            diagonal,
+        }
+    }
+
+    /// Squares that a piece moving from `source` attacks the king from
+    fn direct_checks(
+        &self,
+        piece_type: &impl PieceType,
+        king: Square,
+        source: Square,
+        mut occupancy: Bitboard,
+        flags: BoardFlags,
+    ) -> Bitboard {
+        occupancy.reset(source);
+        match piece_type.value() {
+            PieceTypeV::Pawn => Pawn.attacks(king, occupancy, self.player.opponent(), flags),
+            // Includes the king moving next to the other king, which is never legal
+            _ => piece_type.attacks(king, occupancy, self.player, flags),
+        }
+    }
+
+    /// Castling moves that leave the rook, or a slider the king was in the way of, attacking the
+    /// king
+    fn castling_checks(
+        &self,
+        king: Square,
+        source: Square,
+        occupancy: Bitboard,
+        flags: BoardFlags,
+    ) -> Bitboard {
+        let mut checks = bitboards::EMPTY;
+        for target in King
+            .other_moves(source, occupancy, self.player, flags)
+            .squares()
+        {
+            let (rook_from, rook_to) = if target.file() == File::KINGSIDE {
+                (File::H, File::F)
+            } else {
+                (File::A, File::D)
+            };
+            let rook_from = Square::new(rook_from, source.rank());
+            let rook_to = Square::new(rook_to, source.rank());
+
+            let mut occupancy = occupancy;
+            occupancy.reset(source);
+            occupancy.reset(rook_from);
+            occupancy.set(target);
+            occupancy.set(rook_to);
+            let straight = (self.straight & !Bitboard::from(rook_from)) | Bitboard::from(rook_to);
+
+            let attackers = (Rook.magic_moves(king, occupancy) & straight)
+                | (Bishop.magic_moves(king, occupancy) & self.diagonal);
+            if attackers != bitboards::EMPTY {
+                checks.set(target);
+            }
+        }
+        checks
+    }
+}
+
+impl<P: Player> Movement for CheckingMoves<P> {
+    type Moves = Chain<PieceMoves<P, Self>, pawn::Pushes<P>>;
+
+    fn movement(
+        &self,
+        piece_type: &impl PieceType,
+        source: Square,
+        occupancy: Bitboard,
+        flags: BoardFlags,
+    ) -> Bitboard {
+        let king = match self.king {
+            Some(king) => king,
+            None => return bitboards::EMPTY,
+        };
+
+        let mut checks = self.direct_checks(piece_type, king, source, occupancy, flags);
+        if self.discoverers.get(source) {
+            // Anywhere off the line between the sniper and the king uncovers its attack
+            let line = self
+                .snipers
+                .squares()
+                .map(|sniper| legality::between(king, sniper))
+                .find(|line| line.get(source))
+                .unwrap_or(bitboards::EMPTY);
+            checks |= !line;
+        }
+
+        let mut targets = piece_type.attacks(source, occupancy, self.player, flags) & checks;
+        if piece_type.value() == PieceTypeV::King {
+            targets |= self.castling_checks(king, source, occupancy, flags);
+        }
+        targets & !occupancy
+    }
+
+    fn moves(&self, board: &Board) -> Self::Moves {
+        let mask = !board.occupancy();
+        let pawns = match self.king {
+            Some(king) => pawn::checking_moves(self.player, board, king, self.discoverers),
+            None => pawn::quiet_moves(self.player, board, bitboards::EMPTY),
+        };
+        piece_moves(self.player, board, *self, mask, mask).chain(pawns)
+    }
+}
+
+/// Moves that might get the player out of check: moving the king, or capturing or blocking a
+/// single checking piece. These may still leave the king in check, e.g. by moving a pinned piece.
+#[derive(Copy, Clone)]
+pub struct EvadingMoves<P> {
+    player: P,
+    /// Squares that pieces other than the king can move to
+    evasions: Bitboard,
+}
+
+impl<P: Player> EvadingMoves<P> {
+    fn new(board: &Board, player: P) -> Self {
+        let king = board
+            .bitboard_piece(Piece::new(player, King))
+            .squares()
+            .next();
+        let them = board.occupancy_player(player.opponent().value());
+        let evasions = match king {
+            Some(king) => legality::evasions(board, king, them),
+            None => bitboards::FULL,
+        };
+        Self { player, evasions }
+    }
+}
+
+impl<P: Player> Movement for EvadingMoves<P> {
+    type Moves = Chain<PieceMoves<P, Self>, pawn::Moves<P>>;
+
+    fn movement(
+        &self,
+        piece_type: &impl PieceType,
+        source: Square,
+        occupancy: Bitboard,
+        flags: BoardFlags,
+    ) -> Bitboard {
+        let targets = piece_type.movement(source, occupancy, self.player, flags);
+        if piece_type.value() == PieceTypeV::King {
+            targets
+        } else {
+            targets & self.evasions
+        }
+    }
+
+    fn moves(&self, board: &Board) -> Self::Moves {
+        let mask = !board.occupancy_player(self.player.value());
+        let pieces = piece_moves(self.player, board, *self, mask, mask & self.evasions);
+        pieces.chain(pawn::evasions(self.player, board, self.evasions))
+    }
+}
+
 type Chain6<K, Q, R, B, N, P> = Chain<Chain<Chain<Chain<Chain<K, Q>, R>, B>, N>, P>;
 
+/// Moves of every piece but pawns, which move differently enough to need their own iterators
+type PieceMoves<P, M> = Chain<
+    Chain<
+        Chain<Chain<MovesIter<P, King, M>, MovesIter<P, Queen, M>>, MovesIter<P, Rook, M>>,
+        MovesIter<P, Bishop, M>,
+    >,
+    MovesIter<P, Knight, M>,
+>;
+
+fn piece_moves<P: Player, M: Movement>(
+    player: P,
+    board: &Board,
+    movement: M,
+    king_mask: Bitboard,
+    mask: Bitboard,
+) -> PieceMoves<P, M> {
+    MovesIter::new(board, Piece::new(player, King), movement, king_mask)
+        .chain(MovesIter::new(
+            board,
+            Piece::new(player, Queen),
+            movement,
+            mask,
+        ))
+        .chain(MovesIter::new(
+            board,
+            Piece::new(player, Rook),
+            movement,
+            mask,
+        ))
+        .chain(MovesIter::new(
+            board,
+            Piece::new(player, Bishop),
+            movement,
+            mask,
+        ))
+        .chain(MovesIter::new(
+            board,
+            Piece::new(player, Knight),
+            movement,
+            mask,
+        ))
+}
+
 #[cfg(test)]
 #[macro_use]
 mod tests {
@@ -251,7 +746,9 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use crate::board::tests::fen;
+    use crate::strategies::*;
     use crate::{bitboard, mov, White};
+    use proptest::proptest;
 
     use super::*;
 
@@ -272,7 +769,7 @@ mod tests {
 
     #[test]
     fn can_generate_all_possible_starting_moves_for_white() {
-        let mut board = Board::default();
+        let board = Board::default();
 
         assert_moves!(
             board,
@@ -299,50 +796,50 @@ mod tests {
 
     #[test]
     fn cannot_make_a_move_that_leaves_king_in_check() {
-        let mut board = fen("8/8/8/8/8/8/KP5r/8 w");
+        let board = fen("8/8/8/8/8/8/KP5r/8 w");
         // Note that the pawn is not allowed to move
         assert_moves!(board, [a2a1, a2b1, a2a3, a2b3,]);
     }
 
     #[test]
     fn when_checked_by_unprotected_adjacent_pc_then_king_can_move_capture_or_other_can_capture() {
-        let mut board = fen("n7/k7/BP6/8/8/8/8/8 b");
+        let board = fen("n7/k7/BP6/8/8/8/8/8 b");
         assert_moves!(board, [a7b8, a7a6, a7b6, a8b6]);
     }
 
     #[test]
     fn when_checked_by_protected_adjacent_pc_then_king_can_move_or_other_can_capture() {
-        let mut board = fen("n7/k7/BP6/P7/8/8/8/8 b");
+        let board = fen("n7/k7/BP6/P7/8/8/8/8 b");
         assert_moves!(board, [a7b8, a7a6, a8b6]);
     }
 
     #[test]
     fn when_checked_by_distant_ray_piece_then_king_can_move_or_other_can_block_or_capture() {
-        let mut board = fen("kb6/8/8/8/8/8/R7/r7 b");
+        let board = fen("kb6/8/8/8/8/8/R7/r7 b");
         assert_moves!(board, [a8b7, b8a7, a1a2]);
     }
 
     #[test]
     fn when_checked_by_knight_then_king_can_move_or_other_can_capture() {
-        let mut board = fen("k7/b7/1N6/8/8/8/8/8 b");
+        let board = fen("k7/b7/1N6/8/8/8/8/8 b");
         assert_moves!(board, [a8b8, a8b7, a7b6]);
     }
 
     #[test]
     fn when_double_checked_by_distant_pieces_then_king_can_move() {
-        let mut board = fen("k1R5/8/2n5/R7/8/8/8/8 b");
+        let board = fen("k1R5/8/2n5/R7/8/8/8/8 b");
         assert_moves!(board, [a8b7]);
     }
 
     #[test]
     fn when_double_checked_by_adjacent_unprotected_pieces_then_king_can_move_or_capture() {
-        let mut board = fen("kR6/1Bq5/8/8/8/8/8/8 b");
+        let board = fen("kR6/1Bq5/8/8/8/8/8/8 b");
         assert_moves!(board, [a8a7, a8b8]);
     }
 
     #[test]
     fn absolute_pinned_pieces_can_only_move_along_pinned_ray() {
-        let mut board = fen("3R2Q1/1Q1r1q2/2n5/Rp1kb2R/8/3p1b2/8/3R3B b");
+        let board = fen("3R2Q1/1Q1r1q2/2n5/Rp1kb2R/8/3p1b2/8/3R3B b");
         assert_moves!(
             board,
             [d7d8, d7d6, f7g8, f7e6, f3h1, f3g2, f3e4, d3d2, d5d6, d5e6, d5e4, d5d4, d5c4, d5c5]
@@ -388,4 +885,151 @@ mod tests {
             attacks, expect, board
         );
     }
+
+    #[test]
+    fn see_of_undefended_capture_is_the_captured_piece() {
+        let board = fen("4k3/8/8/3r4/8/8/8/3RK3 w");
+        assert_eq!(board.see(mov!(d1d5)), see_value(PieceTypeV::Rook));
+    }
+
+    #[test]
+    fn see_of_capturing_a_defended_pawn_with_a_queen_loses_material() {
+        let board = fen("4k3/8/4p3/3p4/8/8/8/3QK3 w");
+        let expected = see_value(PieceTypeV::Pawn) - see_value(PieceTypeV::Queen);
+        assert_eq!(board.see(mov!(d1d5)), expected);
+    }
+
+    #[test]
+    fn see_includes_pieces_behind_the_first_attacker() {
+        // Rook takes, pawn takes back, second rook takes the pawn
+        let board = fen("4k3/8/2p5/3n4/8/8/3R4/3RK3 w");
+        let expected = see_value(PieceTypeV::Knight) - see_value(PieceTypeV::Rook)
+            + see_value(PieceTypeV::Pawn);
+        assert_eq!(board.see(mov!(d2d5)), expected);
+
+        // Without the pawn recapture, the knight is just won
+        let board = fen("4k3/8/8/3n4/8/8/3R4/3RK3 w");
+        assert_eq!(board.see(mov!(d2d5)), see_value(PieceTypeV::Knight));
+    }
+
+    #[test]
+    fn see_stops_recapturing_when_it_would_lose_material() {
+        // Taking back the pawn with the queen would lose the queen to the rook
+        let board = fen("3rk3/8/8/3q4/8/8/3P4/3RK3 b");
+        assert_eq!(
+            board.see(mov!(d5d2)),
+            see_value(PieceTypeV::Pawn) - see_value(PieceTypeV::Queen)
+        );
+    }
+
+    #[test]
+    fn see_of_quiet_move_to_safe_square_is_zero() {
+        let board = fen("4k3/8/8/8/8/8/8/3RK3 w");
+        assert_eq!(board.see(mov!(d1d5)), 0);
+    }
+
+    #[test]
+    fn king_cannot_recapture_on_a_defended_square() {
+        let board = fen("4k3/8/8/8/8/8/3r4/2r1K3 b");
+        assert_eq!(board.see(mov!(d2d1)), 0);
+        let board = fen("4k3/8/8/8/8/8/8/3rK3 w");
+        assert_eq!(board.see(mov!(e1d1)), see_value(PieceTypeV::Rook));
+        let board = fen("4k3/8/8/8/8/8/3r4/3rK3 w");
+        assert_eq!(
+            board.see(mov!(e1d1)),
+            see_value(PieceTypeV::Rook) - see_value(PieceTypeV::King)
+        );
+    }
+
+    fn sorted(moves: impl Iterator<Item = Move>) -> Vec<Move> {
+        let mut moves: Vec<Move> = moves.collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn checking_moves_include_discovered_checks() {
+        let board = fen("4k3/8/8/8/4N3/8/8/4R1K1 w");
+        assert_eq!(
+            sorted(board.checking_moves(White)),
+            sorted(
+                board
+                    .quiet_moves(White)
+                    .filter(|mov| mov.from() == Square::E4)
+            )
+        );
+
+        let board = fen("7k/8/8/8/8/8/1P6/B5K1 w");
+        assert_eq!(
+            sorted(board.checking_moves(White)),
+            vec![mov!(b2b3), mov!(b2b4)]
+        );
+    }
+
+    #[test]
+    fn checking_moves_include_castling_into_check() {
+        let board = fen("3k4/8/8/8/8/8/8/R3K3 w Q");
+        assert_eq!(
+            sorted(board.checking_moves(White)),
+            sorted(vec![mov!(a1a8), mov!(a1d1), mov!(e1c1)].into_iter())
+        );
+    }
+
+    #[test]
+    fn evasions_only_block_or_capture_a_single_checker() {
+        let board = fen("4k3/8/8/8/4r3/8/2N5/4K3 w");
+        assert_eq!(
+            sorted(board.evasions(White).filter(|mov| mov.from() != Square::E1)),
+            vec![mov!(c2e3)]
+        );
+
+        let board = fen("4k3/8/8/8/4r3/3n4/2N5/4K3 w");
+        assert!(board.evasions(White).all(|mov| mov.from() == Square::E1));
+    }
+
+    proptest! {
+        #[test]
+        fn quiet_capturing_and_promoting_moves_are_all_pseudo_legal_moves(board in arb_board()) {
+            let player = board.player();
+            let moves = board
+                .quiet_moves(player)
+                .chain(board.capturing_moves(player))
+                .chain(board.promoting_moves(player));
+            assert_eq!(sorted(moves), sorted(board.pseudo_legal_moves()), "\n{:?}", board);
+        }
+
+        #[test]
+        fn checking_moves_are_quiet_moves_that_give_check(mut board in legal_board(0..60)) {
+            let player = board.player();
+            let quiet: Vec<Move> = board.quiet_moves(player).collect();
+            let expected = quiet.into_iter().filter(|mov| {
+                let pmov = board.make_move(*mov);
+                let check = board.in_check(player.opponent());
+                board.unmake_move(pmov);
+                check
+            });
+            let expected = sorted(expected);
+            assert_eq!(sorted(board.checking_moves(player)), expected, "\n{:?}", board);
+        }
+
+        #[test]
+        fn legal_evasions_are_all_legal_moves(board in arb_board()) {
+            let legality = Legality::new(&board);
+            let evasions = board
+                .evasions(board.player())
+                .filter(|mov| legality.is_legal(&board, *mov));
+            assert_eq!(sorted(evasions), sorted(board.moves()), "\n{:?}", board);
+        }
+
+        #[test]
+        fn moves_from_other_positions_are_pseudo_legal_if_generated(
+            board in arb_board(),
+            other in arb_board(),
+        ) {
+            let moves: HashSet<Move> = board.pseudo_legal_moves().collect();
+            for mov in board.pseudo_legal_moves().chain(other.pseudo_legal_moves()) {
+                assert_eq!(board.is_pseudo_legal(mov), moves.contains(&mov), "{}\n{:?}", mov, board);
+            }
+        }
+    }
 }
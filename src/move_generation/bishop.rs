@@ -51,7 +51,7 @@ mod tests {
 
     #[test]
     fn bishop_can_move_and_capture_diagonally() {
-        let mut board = fen("8/8/8/2p5/2P5/1B6/8/3b4 w");
+        let board = fen("8/8/8/2p5/2P5/1B6/8/3b4 w");
         assert_moves!(board, [b3d1, b3a2, b3c2, b3a4,]);
     }
 }
@@ -79,19 +79,25 @@ mod tests {
 
     #[test]
     fn king_can_move_and_capture_one_square_in_any_direction() {
-        let mut board = fen("8/8/8/8/8/1Kp5/2P5/8 w");
+        let board = fen("8/8/8/8/8/1Kp5/2P5/8 w");
         // b3b2 is missing because it puts the king in check
         assert_moves!(board, [b3a2, b3a3, b3c3, b3a4, b3b4, b3c4,]);
     }
 
+    #[test]
+    fn king_cannot_move_next_to_the_other_king() {
+        let board = fen("8/8/8/8/2k5/8/2K5/8 w");
+        assert_moves!(board, [c2b1, c2c1, c2d1, c2b2, c2d2]);
+    }
+
     #[test]
     fn king_can_castle() {
-        let mut board = fen("8/8/8/8/8/8/r6r/R3K2R w");
+        let board = fen("8/8/8/8/8/8/r6r/R3K2R w");
         assert_moves!(
             board,
             [e1c1, e1g1, e1d1, e1f1, a1b1, a1c1, a1d1, a1a2, h1g1, h1f1, h1h2]
         );
-        let mut board = fen("r3k2r/R6R/8/8/8/8/8/8 b");
+        let board = fen("r3k2r/R6R/8/8/8/8/8/8 b");
         assert_moves!(
             board,
             [e8c8, e8g8, e8d8, e8f8, a8b8, a8c8, a8d8, a8a7, h8g8, h8f8, h8h7]
@@ -100,20 +106,20 @@ mod tests {
 
     #[test]
     fn king_cannot_castle_out_of_check() {
-        let mut board = fen("8/8/8/8/8/8/r2q3r/R3K2R w");
+        let board = fen("8/8/8/8/8/8/r2q3r/R3K2R w");
         assert_moves!(board, [e1f1]);
-        let mut board = fen("r3k2r/R2Q3R/8/8/8/8/8/8 b");
+        let board = fen("r3k2r/R2Q3R/8/8/8/8/8/8 b");
         assert_moves!(board, [e8f8]);
     }
 
     #[test]
     fn king_cannot_castle_into_check() {
-        let mut board = fen("8/8/8/8/8/8/r6p/R3K2R w");
+        let board = fen("8/8/8/8/8/8/r6p/R3K2R w");
         assert_moves!(
             board,
             [e1c1, e1d1, e1f1, a1b1, a1c1, a1d1, a1a2, h1g1, h1f1, h1h2]
         );
-        let mut board = fen("r3k2r/RP5R/8/8/8/8/8/8 b");
+        let board = fen("r3k2r/RP5R/8/8/8/8/8/8 b");
         assert_moves!(
             board,
             [e8g8, e8d8, e8f8, a8b8, a8c8, a8d8, a8a7, h8g8, h8f8, h8h7]
@@ -122,12 +128,12 @@ mod tests {
 
     #[test]
     fn king_cannot_castle_through_check() {
-        let mut board = fen("8/8/8/8/8/8/r5pr/R3K2R w");
+        let board = fen("8/8/8/8/8/8/r5pr/R3K2R w");
         assert_moves!(
             board,
             [e1c1, e1d1, a1b1, a1c1, a1d1, a1a2, h1g1, h1f1, h1h2]
         );
-        let mut board = fen("r3k2r/R1P4R/8/8/8/8/8/8 b");
+        let board = fen("r3k2r/R1P4R/8/8/8/8/8/8 b");
         assert_moves!(
             board,
             [e8g8, e8f8, a8b8, a8c8, a8d8, a8a7, h8g8, h8f8, h8h7]
@@ -136,12 +142,12 @@ mod tests {
 
     #[test]
     fn rook_can_castle_through_an_attacked_square() {
-        let mut board = fen("8/8/8/8/8/8/p6r/R3K2R w");
+        let board = fen("8/8/8/8/8/8/p6r/R3K2R w");
         assert_moves!(
             board,
             [e1c1, e1g1, e1d1, e1f1, a1b1, a1c1, a1d1, a1a2, h1g1, h1f1, h1h2]
         );
-        let mut board = fen("r3k2r/P6R/8/8/8/8/8/8 b");
+        let board = fen("r3k2r/P6R/8/8/8/8/8/8 b");
         assert_moves!(
             board,
             [e8c8, e8g8, e8d8, e8f8, a8b8, a8c8, a8d8, a8a7, h8g8, h8f8, h8h7]
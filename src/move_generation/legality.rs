@@ -0,0 +1,263 @@
+use crate::magic::Magic;
+use crate::move_generation::{Bishop, King, Rook};
+use crate::piece::Piece;
+use crate::{bitboards, Bitboard, Board, File, Move, PieceTypeV, Player, PlayerV, Square};
+
+/// Everything needed to tell whether a pseudo-legal move leaves the player's own king in check,
+/// worked out once per position so moves can be checked without making them.
+pub(crate) struct Legality {
+    player: PlayerV,
+    /// The player's king, if they have one
+    king: Option<Square>,
+    /// The player's pieces that can't leave the line between their king and an opponent slider
+    pinned: Bitboard,
+    /// Squares that pieces other than the king can move to, see [evasions]
+    evasions: Bitboard,
+}
+
+impl Legality {
+    pub fn new(board: &Board) -> Self {
+        let player = board.player();
+        let king = board
+            .bitboard_piece(Piece::new(player, King))
+            .squares()
+            .next();
+
+        let king = match king {
+            Some(king) => king,
+            None => {
+                return Self {
+                    player,
+                    king: None,
+                    pinned: bitboards::EMPTY,
+                    evasions: bitboards::FULL,
+                }
+            }
+        };
+
+        let occupancy = board.occupancy();
+        let us = board.occupancy_player(player);
+        let them = board.occupancy_player(player.opponent());
+        let pieces = board.piece_boards();
+        let queens = pieces[PieceTypeV::Queen];
+        let rooks = (pieces[PieceTypeV::Rook] | queens) & them;
+        let bishops = (pieces[PieceTypeV::Bishop] | queens) & them;
+
+        // Sliders that would attack the king if it weren't for our pieces in the way
+        let snipers =
+            (Rook.magic_moves(king, them) & rooks) | (Bishop.magic_moves(king, them) & bishops);
+        let mut pinned = bitboards::EMPTY;
+        for sniper in snipers.squares() {
+            let blockers = between(king, sniper) & occupancy;
+            if blockers.count() == 1 {
+                pinned |= blockers & us;
+            }
+        }
+
+        Self {
+            player,
+            king: Some(king),
+            pinned,
+            evasions: evasions(board, king, them),
+        }
+    }
+
+    /// Whether a pseudo-legal move of the player to move is legal
+    pub fn is_legal(&self, board: &Board, mov: Move) -> bool {
+        let king = match self.king {
+            Some(king) => king,
+            None => return true,
+        };
+        let (from, to) = (mov.from(), mov.to());
+
+        if from == king {
+            return self.is_legal_king_move(board, mov);
+        }
+
+        let pawn = board[from].is_some_and(|piece| piece.piece_type == PieceTypeV::Pawn);
+        if pawn && from.file() != to.file() && board[to].is_none() {
+            // Taking en passant removes two pieces from the same rank, which can uncover a check
+            // along it that no pin would have found
+            let captured = Square::new(to.file(), from.rank());
+            return self.king_safe_after(board, king, from, to, captured);
+        }
+
+        self.evasions.get(to)
+            && (!self.pinned.get(from) || self.king_safe_after(board, king, from, to, to))
+    }
+
+    fn is_legal_king_move(&self, board: &Board, mov: Move) -> bool {
+        let (from, to) = (mov.from(), mov.to());
+        let them = board.occupancy_player(self.player.opponent());
+
+        let castling = (from.file() - to.file()).abs() == 2;
+        if castling {
+            let through = if to.file() == File::KINGSIDE {
+                self.player.castle_kingside_through()
+            } else {
+                self.player.castle_queenside_through()
+            };
+            let occupancy = board.occupancy();
+            let attacked = through
+                .squares()
+                .any(|square| board.attackers_to(square, occupancy) & them != bitboards::EMPTY);
+            if attacked {
+                return false;
+            }
+        }
+
+        // The king mustn't hide from a slider behind itself
+        let mut occupancy = board.occupancy();
+        occupancy.reset(from);
+        board.attackers_to(to, occupancy) & them == bitboards::EMPTY
+    }
+
+    /// Whether the king is safe after moving a piece from `from` to `to`, capturing anything on
+    /// `captured`
+    fn king_safe_after(
+        &self,
+        board: &Board,
+        king: Square,
+        from: Square,
+        to: Square,
+        captured: Square,
+    ) -> bool {
+        let mut occupancy = board.occupancy();
+        occupancy.reset(from);
+        occupancy.reset(captured);
+        occupancy.set(to);
+        let them = board.occupancy_player(self.player.opponent()) & !Bitboard::from(captured);
+        board.attackers_to(king, occupancy) & them == bitboards::EMPTY
+    }
+}
+
+/// Squares that pieces other than the king on `king` can move to: anywhere when not in check, onto
+/// the checking piece or in its way when in check, and nowhere in double check
+pub(super) fn evasions(board: &Board, king: Square, them: Bitboard) -> Bitboard {
+    let checkers = board.attackers_to(king, board.occupancy()) & them;
+    match checkers.count() {
+        0 => bitboards::FULL,
+        1 => checkers | between(king, checkers.first_set()),
+        _ => bitboards::EMPTY,
+    }
+}
+
+/// Squares strictly between two squares on the same rank, file or diagonal, or nothing if they
+/// don't share one
+pub(super) fn between(a: Square, b: Square) -> Bitboard {
+    let (a_board, b_board) = (Bitboard::from(a), Bitboard::from(b));
+
+    let rook_ray = Rook.magic_moves(a, b_board);
+    if rook_ray.get(b) {
+        return rook_ray & Rook.magic_moves(b, a_board);
+    }
+
+    let bishop_ray = Bishop.magic_moves(a, b_board);
+    if bishop_ray.get(b) {
+        return bishop_ray & Bishop.magic_moves(b, a_board);
+    }
+
+    bitboards::EMPTY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tests::fen;
+    use crate::mov;
+    use crate::strategies::*;
+    use pretty_assertions::assert_eq;
+    use proptest::proptest;
+
+    /// The slow way: make the move and see if the king is attacked
+    fn leaves_king_safe(board: &mut Board, mov: Move) -> bool {
+        let player = board.player();
+        let castling = board[mov.from()].is_some_and(|piece| piece.piece_type == PieceTypeV::King)
+            && (mov.from().file() - mov.to().file()).abs() == 2;
+        if castling {
+            let through = if mov.to().file() == File::KINGSIDE {
+                player.castle_kingside_through()
+            } else {
+                player.castle_queenside_through()
+            };
+            if through & board.attacks(player.opponent()) != bitboards::EMPTY {
+                return false;
+            }
+        }
+
+        let pmov = board.make_move(mov);
+        let safe = !board.in_check(player);
+        board.unmake_move(pmov);
+        safe
+    }
+
+    fn squares(squares: &[Square]) -> Bitboard {
+        squares.iter().fold(bitboards::EMPTY, |bitboard, square| {
+            bitboard | Bitboard::from(*square)
+        })
+    }
+
+    #[test]
+    fn squares_between_are_on_the_shared_line() {
+        assert_eq!(
+            between(Square::A1, Square::A4),
+            squares(&[Square::A2, Square::A3])
+        );
+        assert_eq!(
+            between(Square::H8, Square::E5),
+            squares(&[Square::G7, Square::F6])
+        );
+        assert_eq!(between(Square::A1, Square::B3), bitboards::EMPTY);
+        assert_eq!(between(Square::A1, Square::B2), bitboards::EMPTY);
+    }
+
+    #[test]
+    fn pinned_pieces_are_found() {
+        let board = fen("4k3/8/8/8/1b6/8/3N4/r1P1K3 w");
+        let legality = Legality::new(&board);
+        assert_eq!(legality.pinned, squares(&[Square::D2, Square::C1]));
+        assert_eq!(legality.evasions, bitboards::FULL);
+    }
+
+    #[test]
+    fn evasions_block_or_capture_a_single_checker() {
+        let board = fen("4k3/8/8/8/4r3/8/8/4K3 w");
+        assert_eq!(
+            Legality::new(&board).evasions,
+            squares(&[Square::E2, Square::E3, Square::E4])
+        );
+
+        let board = fen("4k3/8/8/8/8/3n4/8/4K2r w");
+        assert_eq!(Legality::new(&board).evasions, bitboards::EMPTY);
+    }
+
+    #[test]
+    fn en_passant_cannot_uncover_a_check_along_the_rank() {
+        let board = fen("8/8/8/K2pP2r/8/8/8/7k w - d6");
+        let legality = Legality::new(&board);
+        assert!(!legality.is_legal(&board, mov!(e5d6)));
+        assert!(legality.is_legal(&board, mov!(e5e6)));
+    }
+
+    proptest! {
+        #[test]
+        fn legality_matches_making_the_move(mut board in arb_board()) {
+            let legality = Legality::new(&board);
+            let moves: Vec<Move> = board.pseudo_legal_moves().collect();
+            for mov in moves {
+                let expected = leaves_king_safe(&mut board, mov);
+                assert_eq!(legality.is_legal(&board, mov), expected, "{}\n{:?}", mov, board);
+            }
+        }
+
+        #[test]
+        fn legality_matches_making_the_move_in_games(mut board in legal_board(0..60)) {
+            let legality = Legality::new(&board);
+            let moves: Vec<Move> = board.pseudo_legal_moves().collect();
+            for mov in moves {
+                let expected = leaves_king_safe(&mut board, mov);
+                assert_eq!(legality.is_legal(&board, mov), expected, "{}\n{:?}", mov, board);
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::Move;
+use arrayvec::ArrayVec;
+
+/// More moves than any position has, even counting pseudo-legal moves
+pub const MAX_MOVES: usize = 256;
+
+/// A list of moves that lives on the stack, so generating moves never allocates.
+///
+/// Each move has a score, so the list can be ordered as moves are taken out of it with
+/// [MoveList::pop_best].
+#[derive(Clone, Default)]
+pub struct MoveList {
+    moves: ArrayVec<[(Move, i32); MAX_MOVES]>,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, mov: Move) {
+        self.push_scored(mov, 0);
+    }
+
+    pub fn push_scored(&mut self, mov: Move, score: i32) {
+        self.moves.push((mov, score));
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.moves.clear();
+    }
+
+    pub fn contains(&self, mov: Move) -> bool {
+        self.iter().any(|m| m == mov)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Move> + '_ {
+        self.moves.iter().map(|(mov, _)| *mov)
+    }
+
+    /// Keep only the moves matching the predicate
+    pub fn retain(&mut self, mut predicate: impl FnMut(Move) -> bool) {
+        self.moves.retain(|(mov, _)| predicate(*mov));
+    }
+
+    /// Score every move in the list
+    pub fn score(&mut self, mut score: impl FnMut(Move) -> i32) {
+        for (mov, mov_score) in &mut self.moves {
+            *mov_score = score(*mov);
+        }
+    }
+
+    /// Remove the move with the highest score.
+    ///
+    /// There's a good chance the search cuts off after the first few moves, so picking the best
+    /// each time is cheaper than sorting them all.
+    pub fn pop_best(&mut self) -> Option<Move> {
+        let (index, _) = self
+            .moves
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, score))| *score)?;
+        Some(self.moves.swap_remove(index).0)
+    }
+}
+
+impl Extend<Move> for MoveList {
+    fn extend<I: IntoIterator<Item = Move>>(&mut self, moves: I) {
+        for mov in moves {
+            self.push(mov);
+        }
+    }
+}
+
+impl std::iter::FromIterator<Move> for MoveList {
+    fn from_iter<I: IntoIterator<Item = Move>>(moves: I) -> Self {
+        let mut list = Self::new();
+        list.extend(moves);
+        list
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = Move;
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, (Move, i32)>, fn(&(Move, i32)) -> Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.moves.iter().map(|(mov, _)| *mov)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mov;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn moves_are_popped_highest_score_first() {
+        let mut moves = MoveList::new();
+        moves.push_scored(mov!(e2e4), 1);
+        moves.push_scored(mov!(d2d4), 3);
+        moves.push_scored(mov!(g1f3), 2);
+
+        let popped: Vec<Move> = std::iter::from_fn(|| moves.pop_best()).collect();
+        assert_eq!(popped, vec![mov!(d2d4), mov!(g1f3), mov!(e2e4)]);
+        assert!(moves.is_empty());
+    }
+
+    #[test]
+    fn scoring_and_retaining_apply_to_every_move() {
+        let mut moves: MoveList = vec![mov!(a2a3), mov!(b2b4), mov!(c2c3)]
+            .into_iter()
+            .collect();
+        moves.retain(|mov| mov != mov!(c2c3));
+        moves.score(|mov| mov.to().rank().to_index() as i32);
+
+        assert_eq!(moves.len(), 2);
+        assert!(!moves.contains(mov!(c2c3)));
+        assert_eq!(moves.pop_best(), Some(mov!(b2b4)));
+    }
+}
@@ -41,6 +41,8 @@ impl PieceTypeT for Pawn {}
 
 pub type Moves<P> = FlatMap<PawnMovesIter<P>, Promotions, fn(Move) -> Promotions>;
 pub type Attacks<P> = FlatMap<PawnCapturesIter<P>, Promotions, fn(Move) -> Promotions>;
+/// Pushes that never reach the last rank, so don't need to be turned into promotions
+pub type Pushes<P> = PawnMovesIter<P>;
 
 pub fn moves<P: Player>(player: P, board: &Board, _: Bitboard) -> Moves<P> {
     PawnMovesIter::from_board(board, player).flat_map(Promotions::new)
@@ -50,6 +52,63 @@ pub fn attacks<P: Player>(player: P, board: &Board, _: Bitboard) -> Attacks<P> {
     PawnCapturesIter::from_board(board, player).flat_map(Promotions::new)
 }
 
+/// Pushes onto `mask` that don't promote
+pub fn quiet_moves<P: Player>(player: P, board: &Board, mask: Bitboard) -> Pushes<P> {
+    let pawns = board.bitboard_piece(Piece::newv(player, Pawn));
+    let (pushes, double_pushes) = move_boards(pawns, board.occupancy(), player);
+    let pushes = pushes & !promotion_rank(player);
+    PawnMovesIter::from_boards(player, pushes & mask, double_pushes & mask, board.flags())
+}
+
+/// Pushes onto the last rank
+pub fn promoting_moves<P: Player>(player: P, board: &Board) -> Moves<P> {
+    let pawns = board.bitboard_piece(Piece::newv(player, Pawn));
+    let (pushes, _) = move_boards(pawns, board.occupancy(), player);
+    let pushes = pushes & promotion_rank(player);
+    PawnMovesIter::from_boards(player, pushes, bitboards::EMPTY, board.flags())
+        .flat_map(Promotions::new)
+}
+
+/// Pushes that don't promote but give check to the king on `king`, either by attacking it or by
+/// getting out of the way of a slider when the pawn is one of the `discoverers`
+pub fn checking_moves<P: Player>(
+    player: P,
+    board: &Board,
+    king: Square,
+    discoverers: Bitboard,
+) -> Pushes<P> {
+    let pawns = board.bitboard_piece(Piece::newv(player, Pawn));
+    let occupancy = board.occupancy();
+    // A pawn pushed along the king's file stays in the way of the slider behind it
+    let discovering = pawns & discoverers & !bitboards::FILES[king.file()];
+    let checks = Pawn.attacks(king, occupancy, player.opponent(), board.flags());
+
+    let (pushes, double_pushes) = move_boards(pawns & !discovering, occupancy, player);
+    let (discovering_pushes, discovering_double_pushes) =
+        move_boards(discovering, occupancy, player);
+    let pushes = (pushes & checks | discovering_pushes) & !promotion_rank(player);
+    let double_pushes = double_pushes & checks | discovering_double_pushes;
+    PawnMovesIter::from_boards(player, pushes, double_pushes, board.flags())
+}
+
+/// Pushes and captures onto `mask`, along with any capture en passant
+pub fn evasions<P: Player>(player: P, board: &Board, mask: Bitboard) -> Moves<P> {
+    let pawns = board.bitboard_piece(Piece::newv(player, Pawn));
+    let (pushes, double_pushes) = move_boards(pawns, board.occupancy(), player);
+    let targets = board.occupancy_player(player.opponent().value()) & mask;
+    PawnMovesIter {
+        player,
+        pushes: (pushes & mask).squares(),
+        double_pushes: (double_pushes & mask).squares(),
+        captures: PawnCapturesIter::new(pawns, targets, player, board.flags()),
+    }
+    .flat_map(Promotions::new)
+}
+
+fn promotion_rank(player: impl Player) -> Bitboard {
+    bitboards::RANKS[player.opponent().back_rank()]
+}
+
 pub struct PawnMovesIter<P> {
     player: P,
     pushes: SquareIterator,
@@ -65,11 +124,7 @@ impl<P: Player> PawnMovesIter<P> {
         player: P,
         flags: BoardFlags,
     ) -> Self {
-        let free_spaces = !occupancy;
-        let pawns_forward = player.advance_bitboard(sources);
-        let pushes = pawns_forward & free_spaces;
-        let double_mask = bitboards::RANKS[player.pawn_rank() + player.multiplier()];
-        let double_pushes = player.advance_bitboard(pushes & double_mask) & free_spaces;
+        let (pushes, double_pushes) = move_boards(sources, occupancy, player);
 
         Self {
             player,
@@ -79,6 +134,21 @@ impl<P: Player> PawnMovesIter<P> {
         }
     }
 
+    /// Just the given pushes, without any captures
+    fn from_boards(
+        player: P,
+        pushes: Bitboard,
+        double_pushes: Bitboard,
+        flags: BoardFlags,
+    ) -> Self {
+        Self {
+            player,
+            pushes: pushes.squares(),
+            double_pushes: double_pushes.squares(),
+            captures: PawnCapturesIter::new(bitboards::EMPTY, bitboards::EMPTY, player, flags),
+        }
+    }
+
     fn from_board(board: &Board, player: P) -> Self {
         let piece = Piece::newv(player, Pawn);
         let pawns = board.bitboard_piece(piece);
@@ -229,37 +299,37 @@ mod tests {
     fn pawn_cannot_move_at_end_of_board() {
         // Such a situation is impossible in normal chess, but it's an edge case that could cause
         // something to go out of bounds.
-        let mut board = fen("8/8/8/8/8/8/8/p7 b");
+        let board = fen("8/8/8/8/8/8/8/p7 b");
         assert_moves!(board, []);
     }
 
     #[test]
     fn pawn_cannot_capture_piece_directly_in_front_of_it() {
-        let mut board = fen("8/5p2/5N2/3p1N2/3Q4/8/8/8 b");
+        let board = fen("8/5p2/5N2/3p1N2/3Q4/8/8/8 b");
         assert_moves!(board, []);
     }
 
     #[test]
     fn pawn_can_capture_pieces_on_diagonal() {
-        let mut board = fen("8/8/8/3p4/2NPN3/8/8/8 b");
+        let board = fen("8/8/8/3p4/2NPN3/8/8/8 b");
         assert_moves!(board, [d5c4, d5e4]);
     }
 
     #[test]
     fn pawn_cannot_capture_same_player_pieces() {
-        let mut board = fen("8/8/8/3p4/3Pp3/4P3/8/8 b");
+        let board = fen("8/8/8/3p4/3Pp3/4P3/8/8 b");
         assert_moves!(board, []);
     }
 
     #[test]
     fn pawn_cannot_double_push_if_blocked() {
-        let mut board = fen("8/8/8/8/8/p7/P7/8 w");
+        let board = fen("8/8/8/8/8/p7/P7/8 w");
         assert_moves!(board, []);
     }
 
     #[test]
     fn pawn_cannot_double_push_when_not_at_initial_position() {
-        let mut board = fen("8/8/8/8/8/P7/8/8 w");
+        let board = fen("8/8/8/8/8/P7/8/8 w");
         assert_moves!(board, [a3a4]);
     }
 
@@ -279,13 +349,13 @@ mod tests {
 
     #[test]
     fn pawn_can_be_promoted_at_end_of_board() {
-        let mut board = fen("8/P7/8/8/8/8/8/8 w");
+        let board = fen("8/P7/8/8/8/8/8/8 w");
         assert_moves!(board, [a7a8N, a7a8B, a7a8R, a7a8Q]);
     }
 
     #[test]
     fn pawn_can_capture_and_promote_at_end_of_board() {
-        let mut board = fen("nq6/P7/8/8/8/8/8/8 w");
+        let board = fen("nq6/P7/8/8/8/8/8/8 w");
         assert_moves!(board, [a7b8N, a7b8B, a7b8R, a7b8Q]);
     }
 }
@@ -139,6 +139,7 @@ impl FromStr for PieceTypeV {
 }
 
 /// Type-level representation of [PieceType].
+#[allow(dead_code)]
 pub trait PieceTypeT: PieceType + Sized + Default {}
 
 pub struct MovesIter<P, PT, M> {
@@ -49,7 +49,7 @@ mod tests {
 
     #[test]
     fn queen_can_move_and_capture_in_all_directions() {
-        let mut board = fen("8/8/1p6/2p5/2P5/1QP5/8/3b4 w");
+        let board = fen("8/8/1p6/2p5/2P5/1QP5/8/3b4 w");
         assert_moves!(
             board,
             [b3d1, b3a2, b3c2, b3a4, b3a3, b3b1, b3b2, b3b4, b3b5, b3b6,]
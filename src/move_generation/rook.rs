@@ -51,7 +51,7 @@ mod tests {
 
     #[test]
     fn rook_can_move_and_capture_along_rank_and_file() {
-        let mut board = fen("8/8/1p6/1P6/8/1Rq5/8/8 w");
+        let board = fen("8/8/1p6/1P6/8/1Rq5/8/8 w");
         assert_moves!(board, [b3b1, b3b2, b3a3, b3c3, b3b4,]);
     }
 }
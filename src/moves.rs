@@ -1,5 +1,7 @@
-use crate::{BoardFlags, File, PieceTypeV, Player, Square};
+use crate::{Board, BoardFlags, File, PieceTypeV, Player, Square};
 use anyhow::{anyhow, Error};
+use serde::{Serialize, Serializer};
+use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
@@ -78,6 +80,12 @@ impl fmt::Display for Move {
     }
 }
 
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl FromStr for Move {
     type Err = Error;
 
@@ -109,6 +117,112 @@ impl FromStr for Move {
     }
 }
 
+/// A [Move] packed into 16 bits, so lots of them can be stored cheaply.
+///
+/// The source square is in bits 0-5, the target square in bits 6-11, the promotion piece in bits
+/// 12-13 and the kind of move in bits 14-15. No move is all zeros, because a move can't go from a
+/// square to itself.
+#[derive(Copy, Clone, Hash, Eq, PartialEq)]
+pub struct PackedMove(u16);
+
+const NORMAL: u16 = 0;
+const PROMOTION: u16 = 1;
+const EN_PASSANT: u16 = 2;
+const CASTLING: u16 = 3;
+
+const PROMOTIONS: [PieceTypeV; 4] = [
+    PieceTypeV::Knight,
+    PieceTypeV::Bishop,
+    PieceTypeV::Rook,
+    PieceTypeV::Queen,
+];
+
+impl PackedMove {
+    /// Pack a move to be played on the given board, also recording whether it's castling or en
+    /// passant, which the move alone doesn't say. Fails for promotions to a king or pawn.
+    pub fn new(board: &Board, mov: Move) -> Result<Self, Error> {
+        let packed = Self::try_from(mov)?;
+        let (from, to) = (mov.from(), mov.to());
+        let kind = match board[from].map(|piece| piece.piece_type) {
+            Some(PieceTypeV::King) if (from.file() - to.file()).abs() == 2 => CASTLING,
+            Some(PieceTypeV::Pawn) if from.file() != to.file() && board[to].is_none() => EN_PASSANT,
+            _ => return Ok(packed),
+        };
+        Ok(Self(packed.0 | kind << 14))
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
+    pub fn from(self) -> Square {
+        Square::from_index((self.0 & 0b11_1111) as u8)
+    }
+
+    pub fn to(self) -> Square {
+        Square::from_index((self.0 >> 6 & 0b11_1111) as u8)
+    }
+
+    pub fn promoting(self) -> Option<PieceTypeV> {
+        (self.kind() == PROMOTION).then(|| PROMOTIONS[(self.0 >> 12 & 0b11) as usize])
+    }
+
+    /// Only known if the move was packed with [PackedMove::new]
+    pub fn is_castling(self) -> bool {
+        self.kind() == CASTLING
+    }
+
+    /// Only known if the move was packed with [PackedMove::new]
+    pub fn is_en_passant(self) -> bool {
+        self.kind() == EN_PASSANT
+    }
+
+    fn kind(self) -> u16 {
+        self.0 >> 14
+    }
+}
+
+/// Fails for promotions to a king or pawn, which parse but can't be played
+impl TryFrom<Move> for PackedMove {
+    type Error = Error;
+
+    fn try_from(mov: Move) -> Result<Self, Self::Error> {
+        let squares = mov.from().to_index() as u16 | (mov.to().to_index() as u16) << 6;
+        match mov.promoting() {
+            None => Ok(Self(squares | NORMAL << 14)),
+            Some(piece_type) => {
+                let promoting = PROMOTIONS
+                    .iter()
+                    .position(|p| *p == piece_type)
+                    .ok_or_else(|| anyhow!("can't promote to {:?}", piece_type))?
+                    as u16;
+                Ok(Self(squares | promoting << 12 | PROMOTION << 14))
+            }
+        }
+    }
+}
+
+impl From<PackedMove> for Move {
+    fn from(packed: PackedMove) -> Self {
+        Self {
+            from: packed.from(),
+            to: packed.to(),
+            promoting: packed.promoting(),
+        }
+    }
+}
+
+impl fmt::Debug for PackedMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mov: Move = (*self).into();
+        write!(f, "PackedMove({}, {:#06x})", mov, self.0)
+    }
+}
+
 #[macro_export]
 macro_rules! mov {
     ($mov:expr) => {
@@ -123,6 +237,18 @@ pub struct PlayedMove {
     pub capture: Option<PieceTypeV>,
     pub en_passant_capture: bool,
     pub flags: BoardFlags,
+    pub hash: u64,
+    pub pawn_hash: u64,
+    pub halfmove_clock: u16,
+}
+
+/// A turn passed without moving, with extra information so it can be un-done.
+/// See [Board::make_null_move](crate::Board::make_null_move).
+#[derive(Debug, Copy, Clone)]
+pub struct PlayedNullMove {
+    pub flags: BoardFlags,
+    pub hash: u64,
+    pub halfmove_clock: u16,
 }
 
 impl PlayedMove {
@@ -131,12 +257,18 @@ impl PlayedMove {
         capture: Option<PieceTypeV>,
         en_passant_capture: bool,
         flags: BoardFlags,
+        hash: u64,
+        pawn_hash: u64,
+        halfmove_clock: u16,
     ) -> Self {
         Self {
             mov,
             capture,
             en_passant_capture,
             flags,
+            hash,
+            pawn_hash,
+            halfmove_clock,
         }
     }
 
@@ -152,7 +284,10 @@ impl PlayedMove {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board::tests::fen;
+    use crate::strategies::*;
     use pretty_assertions::assert_eq;
+    use proptest::proptest;
 
     #[test]
     fn can_create_a_move_from_piece_and_two_squares() {
@@ -188,4 +323,47 @@ mod tests {
     fn move_parse_on_non_move_string_is_none() {
         assert!("PA8".parse::<Move>().is_err())
     }
+
+    #[test]
+    fn packing_a_move_keeps_its_squares_and_promotion() {
+        for mov in [mov!(a1h8), mov!(h8a1), mov!(e7e8q), mov!(b2a1n)] {
+            let packed = PackedMove::try_from(mov).unwrap();
+            assert_eq!(mov, packed.into());
+            assert_eq!(PackedMove::from_bits(packed.bits()), packed);
+            assert_ne!(packed.bits(), 0);
+        }
+    }
+
+    #[test]
+    fn promotions_to_a_king_or_pawn_cannot_be_packed() {
+        assert!(PackedMove::try_from(mov!(e7e8k)).is_err());
+        assert!(PackedMove::try_from(mov!(e7e8p)).is_err());
+        assert!(PackedMove::new(&Board::default(), mov!(e7e8k)).is_err());
+    }
+
+    #[test]
+    fn packing_a_move_on_a_board_records_castling_and_en_passant() {
+        let board = fen("r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6");
+        let castling = PackedMove::new(&board, mov!(e1g1)).unwrap();
+        assert!(castling.is_castling());
+        assert!(!castling.is_en_passant());
+        assert_eq!(mov!(e1g1), castling.into());
+
+        let en_passant = PackedMove::new(&board, mov!(e5d6)).unwrap();
+        assert!(en_passant.is_en_passant());
+        assert_eq!(mov!(e5d6), en_passant.into());
+
+        let normal = PackedMove::new(&board, mov!(h1h8)).unwrap();
+        assert_eq!(normal, PackedMove::try_from(mov!(h1h8)).unwrap());
+        assert!(!normal.is_castling() && !normal.is_en_passant());
+    }
+
+    proptest! {
+        #[test]
+        fn packing_legal_moves_is_lossless((board, mov) in board_and_move(legal_board(0..60))) {
+            let packed = PackedMove::new(&board, mov).unwrap();
+            assert_eq!(mov, packed.into());
+            assert_eq!(packed.promoting(), mov.promoting());
+        }
+    }
 }
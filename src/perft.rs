@@ -1,4 +1,7 @@
-use crate::{Board, Move};
+use crate::{Board, Move, MoveKind, MoveList};
+use anyhow::{anyhow, Context, Error};
+use std::fmt;
+use std::str::FromStr;
 
 impl Board {
     /// Count number of moves to a certain depth.
@@ -10,14 +13,15 @@ impl Board {
 
         let mut count = 0;
 
-        let moves: Vec<Move> = self.moves().collect();
+        let mut moves = MoveList::new();
+        self.generate(MoveKind::Legal, &mut moves);
 
         // Optimisation - skip making and un-making last moves
         if depth == 1 {
             return moves.len();
         }
 
-        for mov in moves {
+        for mov in &moves {
             let pmov = self.make_move(mov);
             count += self.perft(depth - 1);
             self.unmake_move(pmov);
@@ -25,4 +29,276 @@ impl Board {
 
         count
     }
+
+    /// Like [perft](Board::perft), but positions already counted to the same depth are looked up
+    /// in the table instead. Much faster when the same positions are reached by moving in a
+    /// different order.
+    pub fn perft_hashed(&mut self, depth: usize, table: &mut PerftTable) -> usize {
+        if depth <= 1 {
+            return self.perft(depth);
+        }
+
+        let hash = self.hash();
+        if let Some(count) = table.get(hash, depth) {
+            return count;
+        }
+
+        let mut moves = MoveList::new();
+        self.generate(MoveKind::Legal, &mut moves);
+
+        let mut count = 0;
+        for mov in &moves {
+            let pmov = self.make_move(mov);
+            count += self.perft_hashed(depth - 1, table);
+            self.unmake_move(pmov);
+        }
+
+        table.insert(hash, depth, count);
+        count
+    }
+
+    /// Perft counts after each legal move, so a total that disagrees with a reference can be
+    /// narrowed down to the move it came from.
+    ///
+    /// Empty at depth 0, where the only position counted is this one and no move leads to it.
+    pub fn perft_divide(
+        &mut self,
+        depth: usize,
+        mut table: Option<&mut PerftTable>,
+    ) -> Vec<(Move, usize)> {
+        if depth == 0 {
+            return vec![];
+        }
+
+        let mut moves = MoveList::new();
+        self.generate(MoveKind::Legal, &mut moves);
+
+        let mut counts: Vec<(Move, usize)> = moves
+            .iter()
+            .map(|mov| {
+                let pmov = self.make_move(mov);
+                let count = match table.as_deref_mut() {
+                    Some(table) => self.perft_hashed(depth - 1, table),
+                    None => self.perft(depth - 1),
+                };
+                self.unmake_move(pmov);
+                (mov, count)
+            })
+            .collect();
+        counts.sort_by_key(|(mov, _)| mov.to_string());
+        counts
+    }
+}
+
+/// Counts of positions that have already been counted, see [Board::perft_hashed]
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+#[derive(Default, Copy, Clone)]
+struct PerftEntry {
+    hash: u64,
+    depth: usize,
+    count: usize,
+}
+
+impl PerftTable {
+    pub fn new(megabytes: usize) -> Self {
+        let entries = megabytes * 1024 * 1024 / std::mem::size_of::<PerftEntry>();
+        Self {
+            entries: vec![PerftEntry::default(); entries.max(1)],
+        }
+    }
+
+    fn get(&self, hash: u64, depth: usize) -> Option<usize> {
+        let entry = self.entries[self.index(hash)];
+        (entry.hash == hash && entry.depth == depth).then_some(entry.count)
+    }
+
+    fn insert(&mut self, hash: u64, depth: usize, count: usize) {
+        let index = self.index(hash);
+        self.entries[index] = PerftEntry { hash, depth, count };
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.entries.len() as u64) as usize
+    }
+}
+
+/// A position from an EPD perft suite with the expected counts at each depth, e.g.
+/// `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - ;D1 20 ;D2 400`
+#[derive(Debug, Eq, PartialEq)]
+pub struct PerftPosition {
+    pub fen: String,
+    /// Depths and their expected counts
+    pub expected: Vec<(usize, usize)>,
+}
+
+impl FromStr for PerftPosition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split(';');
+        let fen = fields.next().unwrap_or_default().trim().to_string();
+        let expected = fields
+            .map(|field| {
+                let field = field.trim();
+                let (depth, count) = field
+                    .strip_prefix('D')
+                    .and_then(|field| field.split_once(char::is_whitespace))
+                    .ok_or_else(|| anyhow!("Expected depth and count, e.g. D1 20: {}", field))?;
+                let depth = depth.parse().context("Invalid depth")?;
+                let count = count.trim().parse().context("Invalid count")?;
+                Ok((depth, count))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self { fen, expected })
+    }
+}
+
+/// A count from a perft suite that isn't what was expected
+#[derive(Debug, Eq, PartialEq)]
+pub struct PerftMismatch {
+    pub fen: String,
+    pub depth: usize,
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for PerftMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} depth {}: expected {}, got {}",
+            self.fen, self.depth, self.expected, self.actual
+        )
+    }
+}
+
+/// Count every position in an EPD perft suite, one position per line, up to the given depth.
+/// Returns the counts that don't match.
+pub fn perft_suite(
+    suite: &str,
+    max_depth: usize,
+    table: &mut PerftTable,
+) -> Result<Vec<PerftMismatch>, Error> {
+    let mut mismatches = vec![];
+
+    for line in suite.lines().filter(|line| !line.trim().is_empty()) {
+        let position: PerftPosition = line.parse()?;
+        let mut board = Board::from_fen(position.fen.as_str())?;
+
+        for (depth, expected) in position.expected {
+            if depth > max_depth {
+                continue;
+            }
+            let actual = board.perft_hashed(depth, table);
+            if actual != expected {
+                mismatches.push(PerftMismatch {
+                    fen: position.fen.clone(),
+                    depth,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mov;
+    use pretty_assertions::assert_eq;
+
+    /// Counts from https://www.chessprogramming.org/Perft_Results
+    fn assert_perft(fen: &str, expected: &[usize]) {
+        let mut board = Board::from_fen(fen).unwrap();
+        for (depth, expected) in expected.iter().enumerate() {
+            assert_eq!(board.perft(depth + 1), *expected, "depth {}", depth + 1);
+        }
+    }
+
+    #[test]
+    fn perft_with_pins_and_en_passant_along_a_rank() {
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238],
+        );
+    }
+
+    #[test]
+    fn perft_with_promotions_and_castling_out_of_check() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    fn perft_with_discovered_checks() {
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    fn perft_divide_counts_each_root_move() {
+        let mut board = Board::default();
+        let divide = board.perft_divide(3, None);
+        assert_eq!(divide.len(), 20);
+        assert!(divide.contains(&(mov!(e2e4), 600)));
+        assert!(divide.contains(&(mov!(g1h3), 400)));
+        assert_eq!(divide.iter().map(|(_, count)| count).sum::<usize>(), 8902);
+
+        let mut table = PerftTable::new(1);
+        assert_eq!(board.perft_divide(3, Some(&mut table)), divide);
+    }
+
+    #[test]
+    fn hashed_perft_counts_the_same_as_perft() {
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -")
+                .unwrap();
+        let mut table = PerftTable::new(1);
+        for depth in 0..=3 {
+            assert_eq!(board.perft_hashed(depth, &mut table), board.perft(depth));
+        }
+    }
+
+    #[test]
+    fn perft_positions_are_parsed_from_epd() {
+        let position: PerftPosition = "4k3/8/8/8/8/8/8/4K2R w K - ;D1 15 ;D2 66".parse().unwrap();
+        assert_eq!(position.fen, "4k3/8/8/8/8/8/8/4K2R w K -");
+        assert_eq!(position.expected, vec![(1, 15), (2, 66)]);
+
+        assert!("4k3/8/8/8/8/8/8/4K2R w K - ;D1"
+            .parse::<PerftPosition>()
+            .is_err());
+        assert!("4k3/8/8/8/8/8/8/4K2R w K - ;D1 x"
+            .parse::<PerftPosition>()
+            .is_err());
+    }
+
+    #[test]
+    fn perft_suite_reports_mismatched_counts() {
+        let suite = "
+            4k3/8/8/8/8/8/8/4K2R w K - ;D1 15 ;D2 66 ;D3 1197
+            rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 401
+        ";
+        let mut table = PerftTable::new(1);
+        let mismatches = perft_suite(suite, 2, &mut table).unwrap();
+        assert_eq!(
+            mismatches,
+            vec![PerftMismatch {
+                fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+                depth: 2,
+                expected: 401,
+                actual: 400,
+            }]
+        );
+    }
 }
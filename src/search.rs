@@ -1,19 +1,56 @@
+mod ordering;
+mod time;
 mod tree;
 
-use crate::{typed_player, Board, Move, Player};
+use crate::eval::Evaluator;
+use crate::move_generation::{see_value, Legality};
+use crate::{
+    bitboards, typed_player, Board, GameState, Move, MoveKind, MoveList, PieceTypeV, Player,
+};
+use anyhow::{anyhow, Error};
 use arrayvec::ArrayVec;
+use ordering::MoveOrdering;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::{fs, thread};
+use time::TimeManager;
 use tree::SearchTree;
-use ttable::{Key, Node, NodeType, TranspositionTable};
+use ttable::{Node, NodeType, TranspositionTable};
+
+pub use time::{Clock, SearchLimits};
 
 mod ttable;
 
-const HIGH_SCORE: i32 = std::i32::MAX;
+const HIGH_SCORE: i32 = i32::MAX;
 const LOW_SCORE: i32 = -HIGH_SCORE; // Not std::i32::MIN or we get overflows on negation
+/// Value of checkmating the opponent right now. Mates further away are worth a little less, so
+/// we prefer faster mates and delay being mated.
 const WIN: i32 = 1_000_000;
+/// Values within this many plies of [WIN] are mates
+const MAX_MATE_PLY: i32 = 1_000;
+
+/// Half-width of the first aspiration window, in centipawns
+const ASPIRATION_WINDOW: i32 = 25;
+const ASPIRATION_MIN_DEPTH: u16 = 4;
+
+/// Null moves are searched with this much less depth, plus more for deeper searches
+const NULL_MOVE_REDUCTION: u16 = 2;
+const NULL_MOVE_MIN_DEPTH: u16 = 3;
+/// Quiet moves are searched with less depth after this many moves have been tried
+const LATE_MOVE_MIN_MOVES: u32 = 3;
+const LATE_MOVE_MIN_DEPTH: u16 = 3;
+
+/// Captures that can't raise the score to alpha even with this much positional gain on top of the
+/// captured piece aren't searched in quiescence
+const DELTA_MARGIN: i32 = 200;
+
+/// Threads only check for new requests, the time and the shared node count after searching this
+/// many nodes, since it's slow compared to searching a node
+const NODES_PER_CHECK: u64 = 1024;
 
 macro_rules! log_search {
     ($searcher:expr, $depth:expr, $($arg:tt)*) => ({
@@ -26,36 +63,253 @@ macro_rules! log_search {
     })
 }
 
-impl Board {
-    fn key(&self) -> Key {
-        (
-            *self.piece_boards(),
-            *self.player_boards(),
-            self.player(),
-            self.flags(),
-        )
-    }
-}
-
 #[derive(Debug, PartialEq, Eq)]
 enum Request {
     StartSearch {
         board: Box<Board>,
-        target_depth: Option<u16>,
+        history: Vec<u64>,
+        limits: SearchLimits,
+        multi_pv: u16,
     },
-    AbortSearch,
     AbortThread,
 }
 
+/// State shared between the threads of a search
+#[derive(Default)]
+struct SharedState {
+    /// Set to stop every thread searching
+    stop: AtomicBool,
+    /// Nodes searched by every thread, which is updated every [NODES_PER_CHECK] nodes
+    nodes: AtomicU64,
+    /// Result of the last iteration each thread completed
+    results: Mutex<Vec<Option<IterationResult>>>,
+    /// Threads that haven't finished searching yet
+    running: AtomicUsize,
+    /// Result of the whole search, once every thread has finished
+    result: Mutex<Option<SearchResult>>,
+    callbacks: Mutex<SearchCallbacks>,
+    /// Limits that replace those the search started with, e.g. when we were searching
+    /// indefinitely. Each thread picks them up when it next checks in.
+    ponderhit: Mutex<Option<SearchLimits>>,
+}
+
+impl SharedState {
+    fn new_search(&self, num_threads: usize, callbacks: SearchCallbacks) {
+        self.stop.store(false, Ordering::SeqCst);
+        self.nodes.store(0, Ordering::Relaxed);
+        *self.results.lock().unwrap() = vec![None; num_threads];
+        self.running.store(num_threads, Ordering::SeqCst);
+        *self.result.lock().unwrap() = None;
+        *self.callbacks.lock().unwrap() = callbacks;
+        *self.ponderhit.lock().unwrap() = None;
+    }
+
+    fn finish(&self, result: SearchResult) {
+        let on_result = self.callbacks.lock().unwrap().on_result.take();
+        if let Some(on_result) = on_result {
+            on_result(&result);
+        }
+        *self.result.lock().unwrap() = Some(result);
+    }
+
+    /// Choose between the results of each thread.
+    ///
+    /// Threads searching the same position disagree when some have searched deeper than others or
+    /// been sent down different lines by the shared transposition table. Each thread votes for its
+    /// best move, weighted by its depth and how good it thinks the move is, and we pick the thread
+    /// that found the most popular move. A proven mate trumps all, with the fastest mate preferred.
+    fn best_result(&self) -> Option<IterationResult> {
+        let results = self.results.lock().unwrap();
+        let results: Vec<&IterationResult> = results.iter().flatten().collect();
+        let min_value = results.iter().map(|result| result.value).min()?;
+
+        let mut votes: HashMap<Move, i64> = HashMap::new();
+        for result in &results {
+            let weight = (result.value - min_value + 20) as i64 * result.depth as i64;
+            *votes.entry(result.best_move()).or_default() += weight;
+        }
+
+        let is_win = |value: i32| is_mate(value) && value > 0;
+        let best = results.into_iter().reduce(|best, result| {
+            let better = if is_win(best.value) {
+                result.value > best.value
+            } else {
+                is_win(result.value) || votes[&result.best_move()] > votes[&best.best_move()]
+            };
+            if better {
+                result
+            } else {
+                best
+            }
+        })?;
+        Some(best.clone())
+    }
+}
+
+/// The best line found by a thread in an iteration of iterative deepening
+#[derive(Debug, Clone)]
+struct IterationResult {
+    depth: u16,
+    seldepth: u16,
+    value: i32,
+    /// Never empty
+    pv: Vec<Move>,
+}
+
+impl IterationResult {
+    fn best_move(&self) -> Move {
+        self.pv[0]
+    }
+}
+
+/// Outcome of a finished search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// Score of the best move
+    pub score: Score,
+    /// Depth of the last iteration completed by the thread that found the best move
+    pub depth: u16,
+    /// Deepest ply reached by that thread, including quiescence search
+    pub seldepth: u16,
+    /// Nodes searched by all threads
+    pub nodes: u64,
+    /// Time the search took
+    pub time: Duration,
+    /// Best line of play, starting with the best move. Empty if there are no legal moves.
+    pub pv: Vec<Move>,
+}
+
+impl SearchResult {
+    pub fn best_move(&self) -> Option<Move> {
+        self.pv.first().copied()
+    }
+
+    /// The reply we expect, so we can think about it while the opponent does
+    pub fn ponder_move(&self) -> Option<Move> {
+        self.pv.get(1).copied()
+    }
+}
+
+type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+type ResultCallback = Box<dyn FnOnce(&SearchResult) + Send>;
+
+/// Functions called from the search threads as a search started with
+/// [Searcher::go_with_callbacks] progresses
+#[derive(Default)]
+pub struct SearchCallbacks {
+    /// Called with the same info that [Searcher::info] receives
+    pub on_info: Option<InfoCallback>,
+    /// Called once when the search stops
+    pub on_result: Option<ResultCallback>,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum Response {
     StoppedSearch,
 }
 
+/// Statistics about a search, reported after each iteration of iterative deepening
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    /// Which of the best lines this is, starting from 1
+    pub multipv: u16,
+    /// Depth of the completed iteration
+    pub depth: u16,
+    /// Deepest ply reached, including quiescence search
+    pub seldepth: u16,
+    pub score: Score,
+    pub bound: Bound,
+    /// Nodes searched by all threads
+    pub nodes: u64,
+    /// Time since the search started
+    pub time: Duration,
+    /// How full the transposition table is, in permille
+    pub hashfull: u16,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    /// Nodes searched per second
+    pub fn nps(&self) -> u64 {
+        let millis = self.time.as_millis().max(1) as u64;
+        self.nodes * 1000 / millis
+    }
+}
+
+/// Whether a reported score is exact, or only a bound because the search is still looking for the
+/// exact score
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The score is at least this good
+    Lower,
+    /// The score is at most this good
+    Upper,
+}
+
+/// Score of a position from the point of view of the player to move
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Score {
+    /// Advantage in hundredths of a pawn
+    Centipawns(i32),
+    /// Mate in this many moves, negative if the player to move is getting mated
+    Mate(i16),
+}
+
+impl Score {
+    fn new(value: i32) -> Self {
+        if is_mate(value) {
+            let plies = (WIN - value.abs()) as i16;
+            if value > 0 {
+                Score::Mate((plies + 1) / 2)
+            } else {
+                Score::Mate(-plies / 2)
+            }
+        } else {
+            Score::Centipawns(value)
+        }
+    }
+
+    /// Number of moves until mate, negative if the player to move is getting mated
+    pub fn mate_in(self) -> Option<i16> {
+        match self {
+            Score::Mate(moves) => Some(moves),
+            Score::Centipawns(_) => None,
+        }
+    }
+}
+
+fn is_mate(value: i32) -> bool {
+    value.abs() >= WIN - MAX_MATE_PLY
+}
+
+/// Mate values are stored in the transposition table as the distance to mate from the position,
+/// rather than from the root, so they're still correct when reached through a different path
+fn value_to_table(value: i32, ply: u16) -> i32 {
+    if is_mate(value) {
+        value + value.signum() * ply as i32
+    } else {
+        value
+    }
+}
+
+fn value_from_table(value: i32, ply: u16) -> i32 {
+    if is_mate(value) {
+        value - value.signum() * ply as i32
+    } else {
+        value
+    }
+}
+
 pub struct Searcher {
     txs: Vec<Sender<Request>>,
     rxs: Vec<Receiver<Response>>,
+    info_rx: Receiver<SearchInfo>,
     transposition_table: Arc<TranspositionTable>,
+    shared: Arc<SharedState>,
+    multi_pv: u16,
+    /// Which threads haven't reported that they stopped searching
+    searching: Vec<bool>,
 }
 
 fn num_threads() -> u32 {
@@ -68,38 +322,66 @@ fn num_threads() -> u32 {
 
 impl Default for Searcher {
     fn default() -> Self {
-        // Each table entry is 8 bytes
+        // Each table entry is 16 bytes
         const TABLE_SIZE: usize = 20_000_000;
         Self::new(num_threads(), TABLE_SIZE)
     }
 }
 
 fn worker_thread(
+    thread: usize,
     transposition_table: &Arc<TranspositionTable>,
+    shared: &SharedState,
     rx: &Receiver<Request>,
     tx: &Sender<Response>,
+    info_tx: Option<&Sender<SearchInfo>>,
 ) {
-    loop {
-        match rx.recv().unwrap() {
+    // Kept for the life of the thread, so cached pawn structures carry over to the next search
+    let mut evaluator = Evaluator::default();
+
+    // The searcher may have been dropped during a search, closing the channel
+    while let Ok(request) = rx.recv() {
+        match request {
             Request::StartSearch {
                 mut board,
-                target_depth,
+                history,
+                limits,
+                multi_pv,
             } => {
-                let mut searcher =
-                    ThreadSearcher::new(&mut board, transposition_table, rx, target_depth);
+                let mut searcher = ThreadSearcher::new(
+                    thread,
+                    &mut board,
+                    history,
+                    transposition_table,
+                    &mut evaluator,
+                    shared,
+                    info_tx,
+                    limits,
+                    multi_pv,
+                );
                 searcher.run();
-                tx.send(Response::StoppedSearch).unwrap();
+                searcher.share_nodes();
+                // The other threads only help the first, so stop once it's done
+                if thread == 0 {
+                    shared.stop.store(true, Ordering::SeqCst);
+                }
+                // The last thread to finish has the final say
+                if shared.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+                    let result = searcher.result();
+                    shared.finish(result);
+                }
+                let _ = tx.send(Response::StoppedSearch);
             }
             Request::AbortThread => {
                 return;
             }
-            request => panic!("Received unexpected request {:?}", request),
         }
     }
 }
 
 impl Drop for Searcher {
     fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
         for tx in &self.txs {
             let _ = tx.send(Request::AbortThread);
         }
@@ -109,15 +391,29 @@ impl Drop for Searcher {
 impl Searcher {
     pub fn new(num_threads: u32, table_size: usize) -> Self {
         let transposition_table = Arc::new(TranspositionTable::new(table_size));
+        let shared = Arc::new(SharedState::default());
         let mut txs = vec![];
         let mut rxs = vec![];
+        let (info_tx, info_rx) = std::sync::mpsc::channel();
 
-        for _ in 0..num_threads {
+        for i in 0..num_threads {
             let transposition_table = transposition_table.clone();
+            let shared = shared.clone();
             let (req_tx, req_rx) = std::sync::mpsc::channel();
             let (res_tx, res_rx) = std::sync::mpsc::channel();
-
-            thread::spawn(move || worker_thread(&transposition_table, &req_rx, &res_tx));
+            // Only the first thread reports info, otherwise we'd get duplicates
+            let info_tx = if i == 0 { Some(info_tx.clone()) } else { None };
+
+            thread::spawn(move || {
+                worker_thread(
+                    i as usize,
+                    &transposition_table,
+                    &shared,
+                    &req_rx,
+                    &res_tx,
+                    info_tx.as_ref(),
+                )
+            });
 
             txs.push(req_tx);
             rxs.push(res_rx);
@@ -126,52 +422,149 @@ impl Searcher {
         Self {
             txs,
             rxs,
+            info_rx,
             transposition_table,
+            shared,
+            multi_pv: 1,
+            searching: vec![false; num_threads as usize],
         }
     }
 
-    pub fn go(&mut self, board: &Board, target_depth: Option<u16>) {
+    /// Create a searcher with a transposition table using about `megabytes` of memory
+    pub fn with_hash_size(num_threads: u32, megabytes: usize) -> Self {
+        let table_size = megabytes * 1024 * 1024 / TranspositionTable::ENTRY_SIZE;
+        Self::new(num_threads, table_size.max(1))
+    }
+
+    /// Forget everything learned from previous searches
+    pub fn clear_hash(&self) {
+        self.transposition_table.clear();
+    }
+
+    /// Number of best lines to search and report info for
+    pub fn set_multi_pv(&mut self, lines: u16) {
+        self.multi_pv = lines.max(1);
+    }
+
+    /// Start searching from the current position of the game.
+    ///
+    /// The game history is used to avoid (or aim for) draws by repetition. The search stops on its
+    /// own when it reaches the `limits`, or when told to `stop()`.
+    pub fn go(&mut self, state: &GameState, limits: SearchLimits) {
+        self.go_with_callbacks(state, limits, SearchCallbacks::default());
+    }
+
+    /// Same as [go](Searcher::go), calling `callbacks` from the search threads as it progresses
+    pub fn go_with_callbacks(
+        &mut self,
+        state: &GameState,
+        limits: SearchLimits,
+        callbacks: SearchCallbacks,
+    ) {
+        // The threads share state with the search in progress, so it has to finish first
+        if self.is_searching() {
+            self.stop();
+        }
+        // Discard info from any previous search
+        self.info().for_each(drop);
+        self.transposition_table.new_search();
+        self.shared.new_search(self.txs.len(), callbacks);
+
+        let history: Vec<u64> = state.history().collect();
         for tx in &self.txs {
             let start_search = Request::StartSearch {
-                board: Box::new(board.clone()),
-                target_depth,
+                board: Box::new(state.board.clone()),
+                history: history.clone(),
+                limits: limits.clone(),
+                multi_pv: self.multi_pv,
             };
             tx.send(start_search).unwrap();
         }
+        self.searching
+            .iter_mut()
+            .for_each(|searching| *searching = true);
+    }
+
+    /// Apply limits to a search that was started without any, e.g. when the opponent plays the
+    /// move we were pondering on.
+    pub fn ponderhit(&mut self, limits: SearchLimits) {
+        *self.shared.ponderhit.lock().unwrap() = Some(limits);
     }
 
     pub fn stop(&mut self) {
-        for tx in &self.txs {
-            tx.send(Request::AbortSearch).unwrap();
-        }
+        self.shared.stop.store(true, Ordering::SeqCst);
         self.wait();
     }
 
     /// Wait to stop - only call this after `stop()` or if there is a stopping condition!
     pub fn wait(&mut self) {
-        for rx in &self.rxs {
-            match rx.recv().unwrap() {
-                Response::StoppedSearch => {}
+        for (rx, searching) in self.rxs.iter().zip(&mut self.searching) {
+            if *searching {
+                match rx.recv().unwrap() {
+                    Response::StoppedSearch => *searching = false,
+                }
             }
         }
     }
 
-    pub fn principal_variation(&mut self, board: &mut Board) -> Vec<Move> {
-        let pv = self.transposition_table.principal_variation(board);
+    /// True until every thread has stopped searching
+    pub fn is_searching(&mut self) -> bool {
+        for (rx, searching) in self.rxs.iter().zip(&mut self.searching) {
+            if *searching {
+                if let Ok(Response::StoppedSearch) = rx.try_recv() {
+                    *searching = false;
+                }
+            }
+        }
+        self.searching.contains(&true)
+    }
 
-        if cfg!(feature = "log-search2") {
-            println!("Rebuilding search tree");
-            let tree =
-                SearchTree::from_table(board, &self.transposition_table, pv.len() as u16 + 1);
-            println!("Dumping to file search-tree.json");
-            fs::write(
-                "search-tree.json",
-                serde_json::to_string_pretty(&tree).unwrap(),
-            )
-            .unwrap();
+    /// Info reported by the search so far, that hasn't already been received
+    pub fn info(&self) -> impl Iterator<Item = SearchInfo> + '_ {
+        self.info_rx.try_iter()
+    }
+
+    /// Wait up to `timeout` for the search to report info
+    pub fn wait_info(&self, timeout: Duration) -> Option<SearchInfo> {
+        self.info_rx.recv_timeout(timeout).ok()
+    }
+
+    /// Search the current position of the game and wait for the result.
+    ///
+    /// Fails if the `limits` would never stop the search, since nothing else could stop it.
+    pub fn search(
+        &mut self,
+        state: &GameState,
+        limits: SearchLimits,
+    ) -> Result<SearchResult, Error> {
+        if !limits.is_limited() {
+            return Err(anyhow!("search would never stop: {:?}", limits));
         }
+        self.go(state, limits);
+        self.wait();
+        Ok(self
+            .result()
+            .expect("every thread has finished, so there's a result"))
+    }
 
-        pv
+    /// Result of the last search, once it has stopped
+    pub fn result(&self) -> Option<SearchResult> {
+        self.shared.result.lock().unwrap().clone()
+    }
+}
+
+/// Move the bound of a window by `delta` past `value`, opening it up completely once it's wide
+/// enough that there's no point narrowing it
+fn widen(value: i32, delta: i32) -> i32 {
+    const MAX_DELTA: i32 = 1_000;
+    if delta.abs() >= MAX_DELTA {
+        if delta > 0 {
+            HIGH_SCORE
+        } else {
+            LOW_SCORE
+        }
+    } else {
+        value.saturating_add(delta)
     }
 }
 
@@ -180,57 +573,156 @@ impl Searcher {
 const MAX_PV: usize = 32;
 
 struct ThreadSearcher<'a> {
+    /// Index of this thread. The first thread reports info and decides when the search stops.
+    thread: usize,
     board: &'a mut Board,
+    /// Hashes of all positions before the current one, in the game and the current search path
+    history: Vec<u64>,
     transposition_table: &'a Arc<TranspositionTable>,
-    rx: &'a Receiver<Request>,
+    shared: &'a SharedState,
+    info_tx: Option<&'a Sender<SearchInfo>>,
     abort: bool,
-    target_depth: u16,
+    limits: SearchLimits,
+    /// True once the limits have been replaced by those from a ponderhit
+    ponderhit: bool,
+    time: TimeManager,
     max_depth: u16,
     principal_variation: ArrayVec<[Move; MAX_PV]>,
     // true while we are searching the left-most tree (i.e. the principal variation)
     leftmost: bool,
+    multi_pv: u16,
+    /// Root moves already reported as the best move of a line in this iteration
+    excluded_root_moves: Vec<Move>,
+    /// Best move found at the root by the last search
+    best_root_move: Option<Move>,
+    /// Distance from the root of the search
+    ply: u16,
+    seldepth: u16,
+    nodes: u64,
+    /// Nodes already added to the shared count
+    shared_nodes: u64,
+    evaluator: &'a mut Evaluator,
+    ordering: MoveOrdering,
+    /// True if the last move was a null move, so the next one mustn't be
+    after_null_move: bool,
+    /// Plies added to the current line by extensions
+    extensions: u16,
 }
 
 impl<'a> ThreadSearcher<'a> {
+    #[allow(clippy::too_many_arguments)]
     fn new(
+        thread: usize,
         board: &'a mut Board,
+        history: Vec<u64>,
         transposition_table: &'a Arc<TranspositionTable>,
-        rx: &'a Receiver<Request>,
-        target_depth: Option<u16>,
+        evaluator: &'a mut Evaluator,
+        shared: &'a SharedState,
+        info_tx: Option<&'a Sender<SearchInfo>>,
+        limits: SearchLimits,
+        multi_pv: u16,
     ) -> Self {
         Self {
+            thread,
             board,
+            history,
             transposition_table,
-            rx,
+            shared,
+            info_tx,
             abort: false,
-            target_depth: target_depth.unwrap_or(u16::MAX),
+            time: TimeManager::new(&limits),
+            limits,
+            ponderhit: false,
             max_depth: 0,
             principal_variation: ArrayVec::new(),
             leftmost: true,
+            multi_pv,
+            excluded_root_moves: vec![],
+            best_root_move: None,
+            ply: 0,
+            seldepth: 0,
+            nodes: 0,
+            shared_nodes: 0,
+            evaluator,
+            ordering: MoveOrdering::new(thread),
+            after_null_move: false,
+            extensions: 0,
         }
     }
 
     fn run(&mut self) {
-        self.max_depth = 1;
+        if self.board.moves().next().is_none() {
+            // Checkmate or stalemate, so there's nothing to search
+            return;
+        }
+
+        // Half the helper threads start a ply deeper, so the threads spread out over different
+        // depths instead of all searching the same tree
+        self.max_depth = 1 + (self.thread % 2) as u16;
         log_search!(self, self.max_depth, "start search");
 
+        // Score of the best line in the last iteration
+        let mut previous_value = None;
+
         while !self.should_abort() {
             log_search!(self, self.max_depth, "search at depth");
 
-            self.leftmost = true;
+            self.excluded_root_moves.clear();
+            let mut best_score = None;
+
+            for line in 1..=self.multi_pv {
+                let value = if line == 1 {
+                    self.aspiration_search(previous_value)
+                } else {
+                    self.leftmost = false;
+                    self.best_root_move = None;
+                    self.search_root(LOW_SCORE, HIGH_SCORE)
+                };
+
+                if self.abort {
+                    break;
+                }
+
+                // Stop once every legal move has been reported
+                let best_root_move = match self.best_root_move {
+                    Some(mov) => mov,
+                    None => break,
+                };
+
+                // Start from our own best move, since other threads share the transposition table
+                let pv = self.line_principal_variation(best_root_move);
+
+                if line == 1 {
+                    self.principal_variation.clear();
+                    self.principal_variation
+                        .extend(pv.iter().copied().take(MAX_PV));
+                    best_score = Some(Score::new(value));
+                    previous_value = Some(value);
+                    self.shared.results.lock().unwrap()[self.thread] = Some(IterationResult {
+                        depth: self.max_depth,
+                        seldepth: self.seldepth,
+                        value,
+                        pv: pv.clone(),
+                    });
+                }
+
+                self.send_info(line, value, Bound::Exact, pv);
+                self.excluded_root_moves.push(best_root_move);
+            }
+
+            if self.abort {
+                break;
+            }
 
-            typed_player!(self.board.player(), |p| self.search(
-                p,
-                self.max_depth,
-                LOW_SCORE,
-                HIGH_SCORE
-            ));
+            let found_mate = match (best_score, self.limits.mate) {
+                (Some(Score::Mate(moves)), Some(mate)) => moves > 0 && moves as u16 <= mate,
+                _ => false,
+            };
 
-            let pv = self
-                .transposition_table
-                .principal_variation(&mut self.board);
-            self.principal_variation.clear();
-            self.principal_variation.extend(pv);
+            let best_move = self.principal_variation.first().copied();
+            if found_mate || !self.time.iteration_complete(best_move) {
+                break;
+            }
 
             self.max_depth += 1;
         }
@@ -238,72 +730,251 @@ impl<'a> ThreadSearcher<'a> {
         log_search!(self, self.max_depth, "end search");
     }
 
+    /// Search the best line with a narrow window around the score of the last iteration, which
+    /// cuts off much more of the tree. If the score falls outside the window, widen it and search
+    /// again.
+    fn aspiration_search(&mut self, previous_value: Option<i32>) -> i32 {
+        let (mut alpha, mut beta) = match previous_value {
+            Some(value) if self.max_depth >= ASPIRATION_MIN_DEPTH && !is_mate(value) => {
+                (value - ASPIRATION_WINDOW, value + ASPIRATION_WINDOW)
+            }
+            _ => (LOW_SCORE, HIGH_SCORE),
+        };
+        let mut delta = ASPIRATION_WINDOW;
+
+        loop {
+            self.leftmost = true;
+            self.best_root_move = None;
+
+            let value = self.search_root(alpha, beta);
+            if self.abort {
+                return value;
+            }
+
+            if value <= alpha && alpha > LOW_SCORE {
+                log_search!(self, self.max_depth, "fail low, {} <= {}", value, alpha);
+                // None of the moves are as good as we thought, so the old PV is still our best guess
+                let pv = self.principal_variation.to_vec();
+                self.send_info(1, value, Bound::Upper, pv);
+                alpha = widen(value, -delta);
+            } else if value >= beta && beta < HIGH_SCORE {
+                log_search!(self, self.max_depth, "fail high, {} >= {}", value, beta);
+                let pv = self.transposition_table.principal_variation(self.board);
+                self.send_info(1, value, Bound::Lower, pv);
+                beta = widen(value, delta);
+            } else {
+                return value;
+            }
+
+            delta = delta.saturating_mul(2);
+        }
+    }
+
+    fn search_root(&mut self, alpha: i32, beta: i32) -> i32 {
+        typed_player!(self.board.player(), |p| self.search(
+            p,
+            self.max_depth,
+            alpha,
+            beta
+        ))
+    }
+
     // alpha = lower bound for value of child nodes
     // beta = upper bound for value of child nodes
     fn search(&mut self, player: impl Player, depth: u16, mut alpha: i32, mut beta: i32) -> i32 {
         log_search!(self, depth, "search, alpha = {}, beta = {}", alpha, beta);
 
-        let key = self.board.key();
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(self.ply);
+        let after_null_move = std::mem::take(&mut self.after_null_move);
+
+        let root = self.ply == 0;
+        if !root && self.is_draw() {
+            log_search!(self, depth, "draw by repetition or fifty-move rule");
+            return 0;
+        }
+
+        let hash = self.board.hash();
+
+        // When searching for the second-best line (and so on) the root value is only for some of
+        // the moves, so mustn't be shared through the transposition table
+        let excluding = root && !self.excluded_root_moves.is_empty();
+
+        if !root {
+            // Even mating right now is no better than a mate we already found closer to the root
+            alpha = alpha.max(-WIN + self.ply as i32);
+            beta = beta.min(WIN - self.ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
 
         let alpha_orig = alpha;
 
-        if let Some(entry) = self.transposition_table.get(&key) {
+        let entry = self.transposition_table.get(hash);
+
+        // The root is always searched, so we know which move is best
+        if let Some(entry) = entry.filter(|_| !root) {
             if entry.depth >= depth {
+                let entry_value = value_from_table(entry.value, self.ply);
                 match entry.node_type {
                     NodeType::PV => {
-                        return entry.value;
+                        return entry_value;
                     }
                     NodeType::Cut => {
-                        alpha = alpha.max(entry.value);
+                        alpha = alpha.max(entry_value);
                     }
                     NodeType::All => {
-                        beta = beta.min(entry.value);
+                        beta = beta.min(entry_value);
                     }
                 }
 
                 if alpha >= beta {
-                    return entry.value;
+                    return entry_value;
                 }
             }
         }
 
         if depth == 0 {
-            return self.quiesce(player, alpha, beta, 0);
+            let value = self.quiesce(player, alpha, beta, 0);
+            // Record the result so the principal variation can be found even at depth 1
+            if !self.abort {
+                self.record(hash, depth, value, alpha_orig, beta, None);
+            }
+            return value;
+        }
+
+        let in_check = self.board.flags().in_check();
+        // Everywhere but the principal variation is searched with a zero-width window, since we
+        // only need to prove that the moves are worse than the PV
+        let pv_node = alpha.saturating_add(1) < beta;
+
+        if !pv_node && !in_check && !after_null_move && self.null_move_allowed(player, depth, beta)
+        {
+            // If we're still doing well after passing the turn, then any real move is likely to
+            // do even better
+            let reduction = NULL_MOVE_REDUCTION + depth / 4;
+            let pmov = self.board.make_null_move();
+            log_search!(self, depth, "null move:");
+            self.history.push(hash);
+            self.ply += 1;
+            self.after_null_move = true;
+            let null_value = -self.search(
+                player.opponent(),
+                depth.saturating_sub(reduction + 1),
+                -beta,
+                -beta + 1,
+            );
+            self.board.unmake_null_move(pmov);
+            self.history.pop();
+            self.ply -= 1;
+
+            if self.should_abort() {
+                return 0;
+            }
+
+            if null_value >= beta {
+                log_search!(self, depth, "null move cut-off, {} >= {}", null_value, beta);
+                // Don't trust mates found after passing the turn
+                return if is_mate(null_value) {
+                    beta
+                } else {
+                    null_value
+                };
+            }
         }
 
         let mut value = LOW_SCORE;
+        let mut best_move = None;
+        let mut moves_searched = 0;
 
-        // Try the PV if we're still searching the left-most tree
-        let pv = self
+        // Try the PV if we're still searching the left-most tree, otherwise the best move found
+        // last time we were in this position
+        let hash_move = self
             .principal_variation
-            .get((self.max_depth - depth) as usize)
+            .get(self.ply as usize)
             .filter(|_| self.leftmost)
-            .copied();
-        let other_moves = self
-            .board
-            .pseudo_legal_moves_for(player)
-            .filter(|mov| pv != Some(*mov));
-
-        for mov in pv.into_iter().chain(other_moves) {
-            let pmov = match self.board.make_if_legal(mov) {
-                None => continue,
-                Some(pmov) => pmov,
-            };
+            .copied()
+            .or_else(|| entry.and_then(|entry| entry.best_move));
+        // With only one way out of check, the reply is forced so costs us nothing to look deeper
+        let single_reply = in_check && self.board.moves().nth(1).is_none();
+        let legality = Legality::new(self.board);
+        let mut moves = self.ordering.staged(hash_move, self.ply);
+
+        while let Some(mov) = moves.next(self.board, &self.ordering) {
+            if root && self.excluded_root_moves.contains(&mov) {
+                continue;
+            }
+
+            let quiet = ordering::is_quiet(self.board, mov);
+
+            if !legality.is_legal(self.board, mov) {
+                continue;
+            }
+            let pmov = self.board.make_move(mov);
 
             log_search!(self, depth, "{}:", mov);
 
+            // Checks and forced replies are searched a ply deeper, so forcing lines aren't cut off
+            // at the horizon. A line can't be extended by more than the depth of the iteration,
+            // or a perpetual check would be searched forever.
+            let gives_check = self.board.flags().in_check();
+            let extend = (gives_check || single_reply) && self.extensions < self.max_depth;
+            let extension = u16::from(extend);
+            let child_depth = depth - 1 + extension;
+
+            self.history.push(hash);
+            self.ply += 1;
+            self.extensions += extension;
+
             // Evaluate value of move for current player
-            let mov_value = -self.search(
-                player.opponent(),
-                depth - 1,
-                // If our maximum possible score is `x`, then the opponent is guaranteed to
-                // score at least `-x`
-                -beta,
-                // If we're guaranteed a score of `y` already, then the opponent can't possibly
-                // get more than `-y`
-                -alpha,
-            );
+            let mov_value = if moves_searched == 0 {
+                -self.search(
+                    player.opponent(),
+                    child_depth,
+                    // If our maximum possible score is `x`, then the opponent is guaranteed to
+                    // score at least `-x`
+                    -beta,
+                    // If we're guaranteed a score of `y` already, then the opponent can't possibly
+                    // get more than `-y`
+                    -alpha,
+                )
+            } else {
+                // Moves ordered late are unlikely to be good, so start with a shallower search
+                let reduce = quiet
+                    && !pv_node
+                    && !in_check
+                    && !gives_check
+                    && depth >= LATE_MOVE_MIN_DEPTH
+                    && moves_searched >= LATE_MOVE_MIN_MOVES;
+                let reduction = if !reduce {
+                    0
+                } else if depth >= 6 && moves_searched >= 2 * LATE_MOVE_MIN_MOVES {
+                    2
+                } else {
+                    1
+                };
+
+                let mut mov_value = -self.search(
+                    player.opponent(),
+                    child_depth - reduction,
+                    -alpha - 1,
+                    -alpha,
+                );
+                if mov_value > alpha && reduction > 0 {
+                    mov_value = -self.search(player.opponent(), child_depth, -alpha - 1, -alpha);
+                }
+                if mov_value > alpha && mov_value < beta {
+                    // Better than the PV, so we need to know exactly how good it is
+                    mov_value = -self.search(player.opponent(), child_depth, -beta, -alpha);
+                }
+                mov_value
+            };
+            moves_searched += 1;
             self.board.unmake_move(pmov);
+            self.history.pop();
+            self.ply -= 1;
+            self.extensions -= extension;
 
             if self.should_abort() {
                 return 0;
@@ -311,6 +982,13 @@ impl<'a> ThreadSearcher<'a> {
 
             log_search!(self, depth, "{} = {}", mov, mov_value);
 
+            if mov_value > value {
+                best_move = Some(mov);
+                if root {
+                    self.best_root_move = Some(mov);
+                }
+            }
+
             value = value.max(mov_value);
 
             // If value exceeds the old lower-bound, then we can increase the lower-bound
@@ -319,6 +997,7 @@ impl<'a> ThreadSearcher<'a> {
             if alpha >= beta {
                 // We've found a move that reaches our upper-bound, so no point searching further
                 log_search!(self, depth, "cut-off, {} >= {}", alpha, beta);
+                self.ordering.cut_off(self.board, mov, self.ply, depth);
                 break;
             }
 
@@ -326,15 +1005,53 @@ impl<'a> ThreadSearcher<'a> {
         }
 
         let no_legal_moves = value == LOW_SCORE;
+        if no_legal_moves && excluding {
+            return value;
+        }
         if no_legal_moves {
             value = if self.board.checkmate() {
-                -WIN
+                -WIN + self.ply as i32
             } else {
                 0 // In stalemate, so this is a tie
             };
         }
 
-        let node_type = if value <= alpha_orig {
+        if !excluding {
+            self.record(hash, depth, value, alpha_orig, beta, best_move);
+        }
+
+        value
+    }
+
+    /// Whether it's worth trying a null move
+    fn null_move_allowed(&mut self, player: impl Player, depth: u16, beta: i32) -> bool {
+        if depth < NULL_MOVE_MIN_DEPTH || is_mate(beta) {
+            return false;
+        }
+
+        // In the endgame, passing is often the best move (zugzwang) so a null move would tell us
+        // nothing. This is rarely the case while there are pieces other than pawns.
+        let pawns_and_kings = self.board.piece_boards()[PieceTypeV::Pawn]
+            | self.board.piece_boards()[PieceTypeV::King];
+        let pieces = self.board.occupancy_player(player) & !pawns_and_kings;
+        if pieces == bitboards::EMPTY {
+            return false;
+        }
+
+        self.evaluator.evaluate(self.board) >= beta
+    }
+
+    /// Store the value of a node searched with the window `alpha..beta` in the transposition table
+    fn record(
+        &self,
+        hash: u64,
+        depth: u16,
+        value: i32,
+        alpha: i32,
+        beta: i32,
+        best_move: Option<Move>,
+    ) {
+        let node_type = if value <= alpha {
             NodeType::All
         } else if value >= beta {
             NodeType::Cut
@@ -346,13 +1063,12 @@ impl<'a> ThreadSearcher<'a> {
 
         let entry = Node {
             depth,
-            value,
+            value: value_to_table(value, self.ply),
             node_type,
+            best_move,
         };
 
-        self.transposition_table.insert(key, entry);
-
-        value
+        self.transposition_table.insert(hash, entry);
     }
 
     /// Evaluate how "quiescent" (quiet or stable) a board is.
@@ -361,15 +1077,16 @@ impl<'a> ThreadSearcher<'a> {
     /// This helps prevent the AI picking bad moves because the board "looks" good, even if an important
     /// piece could be taken in the next turn.
     fn quiesce(&mut self, player: impl Player, mut alpha: i32, beta: i32, depth: i16) -> i32 {
-        // hard cut-off to depth of quiescent search
-        if depth <= -1 {
-            log_search!(self, depth, "woah that's deep enough");
-            return self.board.eval();
-        }
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(self.ply);
 
-        let moves: Vec<Move>;
+        let mut moves = MoveList::new();
+        let in_check = self.board.in_check(player);
+        // Quiet checks are only tried on the first ply, or we'd never stop checking
+        let try_checks = !in_check && depth == 0;
+        let mut stand_pat = None;
 
-        if self.board.in_check(player) {
+        if in_check {
             // We don't want to use the "standing pat" if we're in check, because it may well be
             // that ANY move is worse than the current state.
             log_search!(
@@ -380,41 +1097,63 @@ impl<'a> ThreadSearcher<'a> {
                 beta
             );
             // When in check, assess all moves that get out of check, not just captures
-            moves = self.board.pseudo_legal_moves_for(player).collect();
+            self.board.generate(MoveKind::Evasions, &mut moves);
         } else {
             // "standing pat" is a heuristic based on current board state.
             // It's assumed that there is always some move that will improve our position, so we use
             // it as our lower-bound.
-            let stand_pat = self.board.eval();
+            let stand_pat_value = self.evaluator.evaluate(self.board);
 
             log_search!(
                 self,
                 depth,
                 "quiesce: pat={}, alpha={}, beta={}",
-                stand_pat,
+                stand_pat_value,
                 alpha,
                 beta
             );
 
-            if stand_pat >= beta {
+            if stand_pat_value >= beta {
                 return beta;
             }
 
-            alpha = alpha.max(stand_pat);
-            moves = self.board.capturing_moves(player).collect();
+            alpha = alpha.max(stand_pat_value);
+            self.board.generate(MoveKind::Captures, &mut moves);
+            if try_checks {
+                self.board.generate(MoveKind::Promotions, &mut moves);
+                self.board.generate(MoveKind::Checks, &mut moves);
+            }
+            stand_pat = Some(stand_pat_value);
         }
 
+        self.ordering.order(self.board, &mut moves, None, self.ply);
+        let legality = Legality::new(self.board);
         let mut no_legal_moves = true;
 
-        for mov in moves {
-            let pmov = match self.board.make_if_legal(mov) {
-                None => continue,
-                Some(pmov) => pmov,
-            };
+        while let Some(mov) = moves.pop_best() {
+            let captured = ordering::capture(self.board, mov);
+
+            if let Some(stand_pat) = stand_pat {
+                // Even winning the piece for free won't get us back to alpha
+                let futile = captured.is_some_and(|captured| {
+                    mov.promoting().is_none()
+                        && stand_pat + see_value(captured) + DELTA_MARGIN < alpha
+                });
+                if futile || self.board.see(mov) < 0 {
+                    continue;
+                }
+            }
+
+            if !legality.is_legal(self.board, mov) {
+                continue;
+            }
+            let pmov = self.board.make_move(mov);
             no_legal_moves = false;
 
             log_search!(self, depth, "trying {}", mov);
+            self.ply += 1;
             let mov_value = -self.quiesce(player.opponent(), -beta, -alpha, depth - 1);
+            self.ply -= 1;
             self.board.unmake_move(pmov);
 
             log_search!(self, depth, "{} = {}", mov, mov_value);
@@ -429,17 +1168,345 @@ impl<'a> ThreadSearcher<'a> {
             }
         }
 
-        if no_legal_moves {
-            -WIN
+        // Without being in check, we only looked at captures so there may be other legal moves
+        if no_legal_moves && in_check {
+            -WIN + self.ply as i32
         } else {
             alpha
         }
     }
 
+    /// Result of the whole search, once every thread has finished
+    fn result(&mut self) -> SearchResult {
+        let best = self.shared.best_result();
+        let mut pv = match &best {
+            Some(best) => best.pv.clone(),
+            None => self.transposition_table.principal_variation(self.board),
+        };
+
+        // The search was stopped before it finished an iteration, but any legal move is better
+        // than none
+        if pv.is_empty() {
+            pv.extend(self.board.moves().next());
+        }
+
+        if cfg!(feature = "log-search2") {
+            println!("Rebuilding search tree");
+            let tree =
+                SearchTree::from_table(self.board, self.transposition_table, pv.len() as u16 + 1);
+            println!("Dumping to file search-tree.json");
+            fs::write(
+                "search-tree.json",
+                serde_json::to_string_pretty(&tree).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let value = match &best {
+            Some(best) => best.value,
+            None if self.board.checkmate() => -WIN,
+            None => 0,
+        };
+
+        SearchResult {
+            score: Score::new(value),
+            depth: best.as_ref().map_or(0, |best| best.depth),
+            seldepth: best.as_ref().map_or(0, |best| best.seldepth),
+            nodes: self.shared.nodes.load(Ordering::SeqCst),
+            time: self.time.elapsed(),
+            pv,
+        }
+    }
+
+    /// Principal variation starting with the given root move
+    fn line_principal_variation(&mut self, mov: Move) -> Vec<Move> {
+        let pmov = self.board.make_move(mov);
+        let mut pv = vec![mov];
+        pv.extend(self.transposition_table.principal_variation(self.board));
+        self.board.unmake_move(pmov);
+        pv
+    }
+
+    fn send_info(&self, multipv: u16, value: i32, bound: Bound, pv: Vec<Move>) {
+        if let Some(info_tx) = self.info_tx {
+            let info = SearchInfo {
+                multipv,
+                depth: self.max_depth,
+                seldepth: self.seldepth,
+                score: Score::new(value),
+                bound,
+                nodes: self.total_nodes(),
+                time: self.time.elapsed(),
+                hashfull: self.transposition_table.hashfull(),
+                pv,
+            };
+            if let Some(on_info) = &mut self.shared.callbacks.lock().unwrap().on_info {
+                on_info(&info);
+            }
+            // The searcher may have been dropped, in which case nobody cares about the info
+            let _ = info_tx.send(info);
+        }
+    }
+
+    /// Whether the current position is drawn by the fifty-move rule or by repetition.
+    ///
+    /// A single repetition is treated as a draw: if repeating was the best thing to do the first
+    /// time, it will be the best thing to do every time.
+    fn is_draw(&self) -> bool {
+        if self.board.halfmove_clock() >= 100 {
+            return true;
+        }
+
+        let hash = self.board.hash();
+        // The previous position has the other player to move, so only check every second one
+        self.history
+            .iter()
+            .rev()
+            .take(self.board.halfmove_clock() as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|h| *h == hash)
+    }
+
+    /// Nodes searched by every thread so far
+    fn total_nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed) + self.nodes - self.shared_nodes
+    }
+
+    /// Add the nodes we've searched to the count shared by all threads
+    fn share_nodes(&mut self) {
+        let new_nodes = self.nodes - self.shared_nodes;
+        self.shared.nodes.fetch_add(new_nodes, Ordering::Relaxed);
+        self.shared_nodes = self.nodes;
+    }
+
     fn should_abort(&mut self) -> bool {
-        self.abort = self.abort
-            || self.max_depth > self.target_depth
-            || self.rx.try_recv() == Ok(Request::AbortSearch);
+        // Checking for requests and the time is relatively slow, so only do it every so often
+        let check_in = self.nodes - self.shared_nodes >= NODES_PER_CHECK;
+        if check_in {
+            self.share_nodes();
+
+            if !self.ponderhit {
+                if let Some(limits) = self.shared.ponderhit.lock().unwrap().clone() {
+                    self.time = TimeManager::new(&limits);
+                    self.limits = limits;
+                    self.ponderhit = true;
+                }
+            }
+        }
+
+        let stopped = self.shared.stop.load(Ordering::Relaxed);
+        let limited = !self.limits.infinite;
+        let depth_reached = limited
+            && self
+                .limits
+                .depth
+                .is_some_and(|depth| self.max_depth > depth);
+        let nodes_reached = limited
+            && self
+                .limits
+                .nodes
+                .is_some_and(|nodes| self.total_nodes() >= nodes);
+        // We always finish the first iteration, so there's a sensible move to play
+        let first_iteration = self.max_depth <= 1;
+        let nodes_reached = nodes_reached && !first_iteration;
+        let time_reached = !first_iteration && check_in && self.time.hard_limit_reached();
+
+        self.abort = self.abort || stopped || depth_reached || nodes_reached || time_reached;
         self.abort
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tests::fen;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn mate_scores_count_moves_not_plies() {
+        assert_eq!(Score::new(WIN - 1), Score::Mate(1));
+        assert_eq!(Score::new(WIN - 3), Score::Mate(2));
+        assert_eq!(Score::new(-WIN + 2), Score::Mate(-1));
+        assert_eq!(Score::new(-WIN), Score::Mate(0));
+        assert_eq!(Score::new(300), Score::Centipawns(300));
+        assert_eq!(Score::new(WIN - 3).mate_in(), Some(2));
+        assert_eq!(Score::new(300).mate_in(), None);
+    }
+
+    #[test]
+    fn mate_values_in_the_table_are_relative_to_the_position() {
+        // Mate in 1 found 4 plies from the root is mate in 1 from that position
+        let value = WIN - 5;
+        assert_eq!(value_to_table(value, 4), WIN - 1);
+        assert_eq!(value_from_table(WIN - 1, 4), value);
+        // Reached from 2 plies closer to the root, the same mate is 2 plies nearer
+        assert_eq!(value_from_table(WIN - 1, 2), WIN - 3);
+
+        assert_eq!(value_to_table(-WIN + 6, 4), -WIN + 2);
+        assert_eq!(value_to_table(150, 4), 150);
+        assert_eq!(value_from_table(-150, 4), -150);
+    }
+
+    #[test]
+    fn checks_and_forced_replies_are_searched_past_the_horizon() {
+        // Smothered mate: Nf7+ Kg8 Nh6+ Kh8 Qg8+ Rxg8 Nf7#
+        let board = fen("r6k/6pp/8/4N3/8/1Q6/6PP/6K1 w");
+        // One thread, otherwise the mate could come from another thread's table entry without
+        // this search ever going past the horizon
+        let mut searcher = Searcher::new(1, 1024);
+        let result = searcher
+            .search(&GameState::new(board), SearchLimits::depth(3))
+            .unwrap();
+        assert_eq!(result.score.mate_in(), Some(4));
+        assert!(result.seldepth > result.depth);
+    }
+
+    #[test]
+    fn threads_sharing_a_table_find_mates_past_the_horizon() {
+        let board = fen("r6k/6pp/8/4N3/8/1Q6/6PP/6K1 w");
+        let mut searcher = Searcher::new(4, 1024);
+        let result = searcher
+            .search(&GameState::new(board), SearchLimits::depth(3))
+            .unwrap();
+        assert_eq!(result.score.mate_in(), Some(4));
+        assert_eq!(result.best_move(), "e5f7".parse().ok());
+    }
+
+    #[test]
+    fn search_returns_the_best_move_and_expected_reply() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let mut searcher = Searcher::new(2, 1024);
+        let result = searcher
+            .search(&GameState::new(board), SearchLimits::depth(4))
+            .unwrap();
+        assert_eq!(result.best_move(), "e4d5".parse().ok());
+        assert_eq!(result.ponder_move(), result.pv.get(1).copied());
+        assert!(result.depth >= 4);
+        assert!(result.nodes > 0);
+        assert_eq!(searcher.result(), Some(result));
+    }
+
+    #[test]
+    fn search_without_legal_moves_has_no_best_move() {
+        let board = fen("k6R/8/1K6/8/8/8/8/8 b");
+        let result = Searcher::new(2, 1024)
+            .search(&GameState::new(board), SearchLimits::depth(3))
+            .unwrap();
+        assert_eq!(result.best_move(), None);
+        assert_eq!(result.score, Score::Mate(0));
+    }
+
+    #[test]
+    fn search_without_limits_is_an_error() {
+        let mut searcher = Searcher::new(1, 1024);
+        let state = GameState::default();
+        assert!(searcher.search(&state, SearchLimits::default()).is_err());
+
+        let infinite = SearchLimits {
+            infinite: true,
+            ..SearchLimits::depth(3)
+        };
+        assert!(searcher.search(&state, infinite).is_err());
+        assert!(!searcher.is_searching());
+    }
+
+    #[test]
+    fn callbacks_are_called_during_an_async_search() {
+        use std::sync::mpsc::channel;
+
+        let (info_tx, info_rx) = channel();
+        let (result_tx, result_rx) = channel();
+        let callbacks = SearchCallbacks {
+            on_info: Some(Box::new(move |info| info_tx.send(info.depth).unwrap())),
+            on_result: Some(Box::new(move |result| {
+                result_tx.send(result.clone()).unwrap()
+            })),
+        };
+
+        let mut searcher = Searcher::new(2, 1024);
+        let state = GameState::new(Board::default());
+        searcher.go_with_callbacks(&state, SearchLimits::depth(3), callbacks);
+
+        let result = result_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(info_rx.try_iter().max(), Some(3));
+        assert!(result.best_move().is_some());
+        searcher.wait();
+        assert_eq!(searcher.result(), Some(result));
+    }
+
+    #[test]
+    fn going_again_stops_the_search_in_progress() {
+        let infinite = SearchLimits {
+            infinite: true,
+            ..SearchLimits::default()
+        };
+        let mut searcher = Searcher::new(2, 1024);
+        searcher.go(&GameState::default(), infinite);
+
+        let mate_in_1 = GameState::new(fen("k7/8/1K6/8/8/8/8/7R w"));
+        searcher.go(&mate_in_1, SearchLimits::depth(2));
+        searcher.wait();
+        let result = searcher.result().unwrap();
+        assert_eq!(result.best_move(), "h1h8".parse().ok());
+        assert!(!searcher.is_searching());
+    }
+
+    #[test]
+    fn ponderhit_limits_a_search_in_progress() {
+        let infinite = SearchLimits {
+            infinite: true,
+            ..SearchLimits::default()
+        };
+        let mut searcher = Searcher::new(2, 1024);
+        searcher.go(&GameState::default(), infinite);
+        searcher.ponderhit(SearchLimits::depth(2));
+        searcher.wait();
+        assert!(searcher.result().unwrap().best_move().is_some());
+    }
+
+    fn result(depth: u16, value: i32, best_move: &str) -> Option<IterationResult> {
+        Some(IterationResult {
+            depth,
+            seldepth: depth,
+            value,
+            pv: vec![best_move.parse().unwrap()],
+        })
+    }
+
+    #[test]
+    fn threads_vote_for_the_best_move() {
+        let shared = SharedState::default();
+        *shared.results.lock().unwrap() = vec![
+            result(8, 30, "e2e4"),
+            result(9, 25, "d2d4"),
+            None,
+            result(8, 20, "d2d4"),
+        ];
+        let best = shared.best_result().unwrap();
+        assert_eq!(best.best_move(), "d2d4".parse().unwrap());
+        assert_eq!(best.depth, 9);
+    }
+
+    #[test]
+    fn fastest_mate_wins_the_vote() {
+        let shared = SharedState::default();
+        *shared.results.lock().unwrap() = vec![
+            result(8, 30, "e2e4"),
+            result(8, WIN - 5, "d2d4"),
+            result(9, WIN - 3, "g1f3"),
+            result(9, 30, "e2e4"),
+        ];
+        assert_eq!(
+            shared.best_result().unwrap().best_move(),
+            "g1f3".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn no_result_without_a_completed_iteration() {
+        let shared = SharedState::default();
+        shared.new_search(2, SearchCallbacks::default());
+        assert!(shared.best_result().is_none());
+    }
+}
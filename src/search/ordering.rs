@@ -0,0 +1,445 @@
+use crate::{Board, Move, MoveKind, MoveList, PieceTypeV, PlayerV, Square, SquareMap};
+use enum_map::EnumMap;
+
+/// Scores for the different kinds of move, so that every move of one kind is tried before any
+/// move of the next
+const BEST_MOVE: i32 = 3_000_000;
+const CAPTURE: i32 = 2_000_000;
+const KILLER: i32 = 1_000_000;
+
+/// History scores are halved when one reaches this, so recent cut-offs count for more and the
+/// scores never reach [KILLER]
+const MAX_HISTORY: i32 = 100_000;
+
+const KILLERS_PER_PLY: usize = 2;
+
+/// Most that quiet moves are nudged up or down the order by, so threads searching together don't
+/// all look at the same moves first
+const PERTURBATION: u64 = 32;
+
+/// Decides which order to try moves in.
+///
+/// A good ordering lets alpha-beta cut off the search sooner, so the best move is tried first,
+/// then captures with the most valuable victim and the least valuable attacker, then "killer" moves
+/// that caused a cut-off at the same ply, then quiet moves by how often they've caused cut-offs.
+pub(crate) struct MoveOrdering {
+    /// Quiet moves that most recently caused a cut-off, indexed by ply
+    killers: Vec<[Option<Move>; KILLERS_PER_PLY]>,
+    /// How good quiet moves have been at causing cut-offs, by player, source and target
+    history: Box<EnumMap<PlayerV, SquareMap<SquareMap<i32>>>>,
+    /// Seed for shuffling quiet moves, or 0 to leave them alone
+    seed: u64,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl MoveOrdering {
+    /// Move ordering for the given search thread. Every thread but the first orders quiet moves
+    /// slightly differently.
+    pub fn new(thread: usize) -> Self {
+        Self {
+            killers: vec![],
+            history: Box::new(EnumMap::from(|_| {
+                SquareMap::from(|_| SquareMap::from(|_| 0))
+            })),
+            seed: thread as u64,
+        }
+    }
+
+    /// Score the moves at the given ply, so [MoveList::pop_best] takes them in order, starting
+    /// with `best_move` if there is one
+    pub fn order(&self, board: &Board, moves: &mut MoveList, best_move: Option<Move>, ply: u16) {
+        let killers = self.killers.get(ply as usize);
+        moves.score(|mov| {
+            if Some(mov) == best_move {
+                BEST_MOVE
+            } else if let Some(victim) = capture(board, mov) {
+                CAPTURE + mvv_lva(victim, board[mov.from()].unwrap().piece_type)
+            } else if mov.promoting().is_some() {
+                CAPTURE
+            } else if killers.is_some_and(|killers| killers.contains(&Some(mov))) {
+                KILLER
+            } else {
+                let player = board[mov.from()].unwrap().player;
+                self.history[player][mov.from()][mov.to()] + self.perturbation(mov)
+            }
+        });
+    }
+
+    /// Generate the moves at the given ply a stage at a time, see [StagedMoves]
+    pub fn staged(&self, hash_move: Option<Move>, ply: u16) -> StagedMoves {
+        StagedMoves {
+            stage: Stage::Start,
+            hash_move,
+            killers: self.killers.get(ply as usize).copied().unwrap_or_default(),
+            ply,
+            moves: MoveList::new(),
+        }
+    }
+
+    /// Remember a move that caused a cut-off at the given ply and depth, so similar positions can
+    /// try it early. Must be called before the move is made.
+    pub fn cut_off(&mut self, board: &Board, mov: Move, ply: u16, depth: u16) {
+        if !is_quiet(board, mov) {
+            // These are already tried early
+            return;
+        }
+
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; KILLERS_PER_PLY]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mov) {
+            killers[1] = killers[0];
+            killers[0] = Some(mov);
+        }
+
+        let player = board[mov.from()].unwrap().player;
+        let score = &mut self.history[player][mov.from()][mov.to()];
+        // Deeper cut-offs save more work
+        *score += depth as i32 * depth as i32;
+        if *score >= MAX_HISTORY {
+            self.age_history();
+        }
+    }
+
+    fn perturbation(&self, mov: Move) -> i32 {
+        if self.seed == 0 {
+            return 0;
+        }
+        let key = (mov.from().to_index() as u64) << 6 | mov.to().to_index() as u64;
+        let hash = (key ^ self.seed << 12).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        (hash >> 32) as i32 % PERTURBATION as i32
+    }
+
+    fn age_history(&mut self) {
+        for (_, from_squares) in self.history.iter_mut() {
+            for from in Square::all() {
+                for to in Square::all() {
+                    from_squares[from][to] /= 2;
+                }
+            }
+        }
+    }
+}
+
+/// The stages of [StagedMoves], in the order they're generated
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Stage {
+    Start,
+    HashMove,
+    Captures,
+    Killers,
+    Quiets,
+    /// Instead of the captures, killers and quiet moves when in check
+    Evasions,
+}
+
+/// Moves generated a stage at a time: the hash move, then captures and promotions, then killers,
+/// then the remaining quiet moves, each stage ordered like [MoveOrdering::order].
+///
+/// The search often cuts off after the hash move or a capture, so the quiet moves are never
+/// generated at all. When in check, every evasion is generated in one stage instead.
+///
+/// This isn't an [Iterator], because each stage needs the board and the move ordering, which the
+/// search is changing between moves.
+pub(crate) struct StagedMoves {
+    stage: Stage,
+    hash_move: Option<Move>,
+    /// Killers for this ply, only the ones that have been tried once the killer stage is reached
+    killers: [Option<Move>; KILLERS_PER_PLY],
+    ply: u16,
+    /// Moves of the current stage that haven't been tried yet
+    moves: MoveList,
+}
+
+impl StagedMoves {
+    /// The next pseudo-legal move to try, generating the next stage if this one is finished
+    pub fn next(&mut self, board: &Board, ordering: &MoveOrdering) -> Option<Move> {
+        loop {
+            if let Some(mov) = self.moves.pop_best() {
+                return Some(mov);
+            }
+
+            let hash_move = self.hash_move;
+            self.stage = match self.stage {
+                Stage::Start => {
+                    if let Some(mov) = hash_move.filter(|mov| board.is_pseudo_legal(*mov)) {
+                        self.moves.push_scored(mov, BEST_MOVE);
+                    }
+                    Stage::HashMove
+                }
+                Stage::HashMove if board.flags().in_check() => {
+                    board.generate(MoveKind::Evasions, &mut self.moves);
+                    self.moves.retain(|mov| Some(mov) != hash_move);
+                    ordering.order(board, &mut self.moves, None, self.ply);
+                    Stage::Evasions
+                }
+                Stage::HashMove => {
+                    board.generate(MoveKind::Captures, &mut self.moves);
+                    board.generate(MoveKind::Promotions, &mut self.moves);
+                    self.moves.retain(|mov| Some(mov) != hash_move);
+                    ordering.order(board, &mut self.moves, None, self.ply);
+                    Stage::Captures
+                }
+                Stage::Captures => {
+                    for killer in &mut self.killers {
+                        *killer = killer.filter(|mov| {
+                            Some(*mov) != hash_move
+                                && board.is_pseudo_legal(*mov)
+                                && is_quiet(board, *mov)
+                        });
+                    }
+                    // The most recent killer is tried first
+                    for (i, killer) in self.killers.iter().flatten().enumerate() {
+                        self.moves.push_scored(*killer, KILLER - i as i32);
+                    }
+                    Stage::Killers
+                }
+                Stage::Killers => {
+                    let killers = self.killers;
+                    board.generate(MoveKind::Quiets, &mut self.moves);
+                    self.moves
+                        .retain(|mov| Some(mov) != hash_move && !killers.contains(&Some(mov)));
+                    ordering.order(board, &mut self.moves, None, self.ply);
+                    Stage::Quiets
+                }
+                Stage::Quiets | Stage::Evasions => return None,
+            };
+        }
+    }
+}
+
+/// Whether a move doesn't change the material on the board, so isn't captures or promotions
+pub(crate) fn is_quiet(board: &Board, mov: Move) -> bool {
+    capture(board, mov).is_none() && mov.promoting().is_none()
+}
+
+/// The type of piece captured by a move, if any
+pub(crate) fn capture(board: &Board, mov: Move) -> Option<PieceTypeV> {
+    match board[mov.to()] {
+        Some(piece) => Some(piece.piece_type),
+        None => {
+            let pawn = board[mov.from()].is_some_and(|p| p.piece_type == PieceTypeV::Pawn);
+            let en_passant = pawn && mov.from().file() != mov.to().file();
+            en_passant.then_some(PieceTypeV::Pawn)
+        }
+    }
+}
+
+/// Most valuable victim, least valuable attacker: winning a queen with a pawn is tried before
+/// winning a pawn with a queen
+fn mvv_lva(victim: PieceTypeV, attacker: PieceTypeV) -> i32 {
+    value(victim) * 10 - value(attacker)
+}
+
+fn value(piece_type: PieceTypeV) -> i32 {
+    match piece_type {
+        PieceTypeV::Pawn => 1,
+        PieceTypeV::Knight => 2,
+        PieceTypeV::Bishop => 3,
+        PieceTypeV::Rook => 4,
+        PieceTypeV::Queen => 5,
+        PieceTypeV::King => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::tests::fen;
+    use crate::strategies::*;
+    use pretty_assertions::assert_eq;
+    use proptest::proptest;
+
+    fn moves(s: &str) -> Vec<Move> {
+        s.split_whitespace().map(|m| m.parse().unwrap()).collect()
+    }
+
+    /// Order the moves and take them all out of the list
+    fn order_moves(
+        ordering: &MoveOrdering,
+        board: &Board,
+        moves: impl IntoIterator<Item = Move>,
+        best_move: Option<Move>,
+        ply: u16,
+    ) -> Vec<Move> {
+        let mut moves: MoveList = moves.into_iter().collect();
+        ordering.order(board, &mut moves, best_move, ply);
+        std::iter::from_fn(|| moves.pop_best()).collect()
+    }
+
+    #[test]
+    fn captures_are_ordered_by_most_valuable_victim_then_least_valuable_attacker() {
+        let board = fen("4k3/8/8/3q1r2/4P3/2N5/8/3RK3 w");
+        let ordering = MoveOrdering::default();
+        let ordered: Vec<Move> = order_moves(
+            &ordering,
+            &board,
+            moves("e1e2 c3d5 e4f5 e4d5 d1d5"),
+            None,
+            0,
+        );
+        assert_eq!(ordered, moves("e4d5 c3d5 d1d5 e4f5 e1e2"));
+    }
+
+    #[test]
+    fn best_move_is_tried_first() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let ordering = MoveOrdering::default();
+        let best = "e1f1".parse().ok();
+        let ordered: Vec<Move> = order_moves(&ordering, &board, moves("e4d5 e1f1"), best, 0);
+        assert_eq!(ordered, moves("e1f1 e4d5"));
+    }
+
+    #[test]
+    fn killers_are_tried_after_captures_and_before_other_quiet_moves() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let mut ordering = MoveOrdering::default();
+        ordering.cut_off(&board, "e1f2".parse().unwrap(), 3, 1);
+
+        let ordered: Vec<Move> = order_moves(&ordering, &board, moves("e1d2 e1f2 e4d5"), None, 3);
+        assert_eq!(ordered, moves("e4d5 e1f2 e1d2"));
+
+        // Killers only apply to the same ply
+        let ordered: Vec<Move> = order_moves(&ordering, &board, moves("e1f1 e1f2"), None, 4);
+        assert_eq!(
+            ordered,
+            moves("e1f2 e1f1"),
+            "history should still prefer e1f2"
+        );
+    }
+
+    #[test]
+    fn helper_threads_shuffle_quiet_moves_but_not_captures() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let quiet = moves("e1d1 e1d2 e1e2 e1f1 e1f2");
+        let orders: Vec<Vec<Move>> = (0..4)
+            .map(|thread| {
+                let all = quiet.iter().copied().chain(moves("e4d5"));
+                order_moves(&MoveOrdering::new(thread), &board, all, None, 0)
+            })
+            .collect();
+
+        for order in &orders {
+            assert_eq!(order[0], "e4d5".parse().unwrap());
+        }
+        assert!(orders.iter().any(|order| order != &orders[0]));
+        assert_eq!(MoveOrdering::new(0).perturbation(quiet[0]), 0);
+    }
+
+    #[test]
+    fn history_prefers_moves_that_caused_deeper_cut_offs() {
+        let board = fen("4k3/8/8/8/8/8/8/4K3 w");
+        let mut ordering = MoveOrdering::default();
+        ordering.cut_off(&board, "e1d1".parse().unwrap(), 0, 1);
+        ordering.cut_off(&board, "e1f1".parse().unwrap(), 0, 3);
+        ordering.killers.clear();
+
+        let ordered: Vec<Move> = order_moves(&ordering, &board, moves("e1d1 e1e2 e1f1"), None, 0);
+        assert_eq!(ordered, moves("e1f1 e1d1 e1e2"));
+    }
+
+    #[test]
+    fn captures_are_not_killers() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let mut ordering = MoveOrdering::default();
+        ordering.cut_off(&board, "e4d5".parse().unwrap(), 0, 1);
+        assert!(ordering.killers.is_empty());
+    }
+
+    fn staged(board: &Board, ordering: &MoveOrdering, best: Option<Move>) -> Vec<Move> {
+        let mut staged = ordering.staged(best, 0);
+        std::iter::from_fn(|| staged.next(board, ordering)).collect()
+    }
+
+    #[test]
+    fn staged_moves_try_the_hash_move_then_captures_then_killers_then_quiet_moves() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let mut ordering = MoveOrdering::default();
+        ordering.cut_off(&board, "e1f2".parse().unwrap(), 0, 1);
+        ordering.cut_off(&board, "e1d1".parse().unwrap(), 0, 1);
+        ordering.cut_off(&board, "e1f1".parse().unwrap(), 1, 2);
+        ordering.cut_off(&board, "e4e5".parse().unwrap(), 2, 1);
+
+        let best = "e1e2".parse().ok();
+        assert_eq!(
+            staged(&board, &ordering, best),
+            moves("e1e2 e4d5 e1d1 e1f2 e1f1 e4e5 e1d2")
+        );
+    }
+
+    #[test]
+    fn staged_moves_skip_a_hash_move_or_killer_that_is_not_pseudo_legal() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let mut ordering = MoveOrdering::default();
+        // A quiet move from another position that's now a capture
+        ordering.cut_off(
+            &fen("4k3/8/8/8/4P3/8/8/4K3 w"),
+            "e4d5".parse().unwrap(),
+            0,
+            1,
+        );
+
+        let staged = staged(&board, &ordering, "e2e4".parse().ok());
+        assert_eq!(staged[0], "e4d5".parse().unwrap());
+        assert_eq!(staged.len(), 7);
+    }
+
+    #[test]
+    fn quiet_moves_are_not_generated_until_captures_have_been_tried() {
+        let board = fen("4k3/8/8/3q4/4P3/8/8/4K3 w");
+        let ordering = MoveOrdering::default();
+        let mut staged = ordering.staged(None, 0);
+        assert_eq!(staged.next(&board, &ordering), "e4d5".parse().ok());
+        assert_eq!(staged.stage, Stage::Captures);
+    }
+
+    #[test]
+    fn staged_moves_in_check_are_evasions() {
+        let board = fen("4k3/8/8/8/4r3/8/2N5/4K3 w");
+        let ordering = MoveOrdering::default();
+        let mut staged = ordering.staged(None, 0);
+        assert_eq!(staged.next(&board, &ordering), "c2e3".parse().ok());
+        assert_eq!(staged.stage, Stage::Evasions);
+    }
+
+    proptest! {
+        #[test]
+        fn staged_moves_include_every_legal_move_once(board in arb_board()) {
+            let ordering = MoveOrdering::default();
+            let best = board.moves().last();
+            let staged = staged(&board, &ordering, best);
+
+            let mut unique = staged.clone();
+            unique.sort_by_key(|mov| mov.to_string());
+            unique.dedup();
+            assert_eq!(unique.len(), staged.len());
+            assert!(staged.iter().all(|mov| board.is_pseudo_legal(*mov)));
+
+            let mut legal: Vec<Move> = staged
+                .into_iter()
+                .filter(|mov| board.check_legal(*mov))
+                .collect();
+            let mut expected: Vec<Move> = board.moves().collect();
+            legal.sort_by_key(|mov| mov.to_string());
+            expected.sort_by_key(|mov| mov.to_string());
+            assert_eq!(legal, expected);
+        }
+
+        #[test]
+        fn ordering_yields_every_move_once(board in arb_board()) {
+            let ordering = MoveOrdering::default();
+            let mut expected: Vec<Move> = board.pseudo_legal_moves().collect();
+            let mut ordered: Vec<Move> = order_moves(&ordering, &board, board.pseudo_legal_moves(), expected.last().copied(), 0);
+            expected.sort_by_key(|mov| mov.to_string());
+            ordered.sort_by_key(|mov| mov.to_string());
+            assert_eq!(ordered, expected);
+        }
+    }
+}
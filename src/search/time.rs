@@ -0,0 +1,238 @@
+use crate::Move;
+use std::time::{Duration, Instant};
+
+/// Time reserved for talking to the GUI and network lag, so we never lose on time by a hair
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// Number of moves we expect to play before the end of the game, if the time control doesn't say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Remaining time of the player to move
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Clock {
+    pub time: Duration,
+    /// Time added after each move
+    pub increment: Duration,
+    /// Moves until the next time control, if there is one
+    pub moves_to_go: Option<u32>,
+}
+
+/// When a search should stop on its own.
+///
+/// With no limits set, the search continues until it is stopped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<u16>,
+    /// Nodes searched by all threads together
+    pub nodes: Option<u64>,
+    /// Stop once a mate in this many moves is found
+    pub mate: Option<u16>,
+    pub movetime: Option<Duration>,
+    pub clock: Option<Clock>,
+    /// Ignore all other limits and search until stopped
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u16) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::default()
+        }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        Self {
+            movetime: Some(movetime),
+            ..Self::default()
+        }
+    }
+
+    /// True if the search will eventually stop without being told to
+    pub fn is_limited(&self) -> bool {
+        !self.infinite
+            && (self.depth.is_some()
+                || self.nodes.is_some()
+                || self.mate.is_some()
+                || self.movetime.is_some()
+                || self.clock.is_some())
+    }
+}
+
+/// Decides when to stop searching based on the time used so far.
+///
+/// The soft limit is checked between iterations of iterative deepening, and is stretched while
+/// the best move keeps changing. The hard limit aborts the search part way through an iteration.
+pub(crate) struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    best_move: Option<Move>,
+    /// How often the best move has changed recently, decaying with each iteration
+    instability: f64,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits) -> Self {
+        let (soft_limit, hard_limit) = if limits.infinite {
+            (None, None)
+        } else if let Some(movetime) = limits.movetime {
+            (Some(movetime), Some(movetime))
+        } else if let Some(clock) = limits.clock {
+            let (soft, hard) = allocate(clock);
+            (Some(soft), Some(hard))
+        } else {
+            (None, None)
+        };
+
+        Self {
+            start: Instant::now(),
+            soft_limit,
+            hard_limit,
+            best_move: None,
+            instability: 0.0,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.hard_limit
+            .is_some_and(|hard_limit| self.elapsed() >= hard_limit)
+    }
+
+    /// Record the best move of a completed iteration, returning `true` if there's time for another
+    pub fn iteration_complete(&mut self, best_move: Option<Move>) -> bool {
+        let changed = self.best_move.is_some() && self.best_move != best_move;
+        self.best_move = best_move;
+        self.instability = self.instability / 2.0 + if changed { 1.0 } else { 0.0 };
+
+        match self.soft_limit {
+            None => true,
+            Some(soft_limit) => {
+                // An unstable best move means we haven't understood the position yet
+                let soft_limit = soft_limit.mul_f64(1.0 + self.instability);
+                let elapsed = self.elapsed();
+                elapsed < soft_limit && !self.hard_limit_reached()
+            }
+        }
+    }
+}
+
+/// Soft and hard limits for a move, given the time on the clock
+fn allocate(clock: Clock) -> (Duration, Duration) {
+    let available = clock.time.saturating_sub(MOVE_OVERHEAD);
+    let moves_to_go = clock.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+    // Never spend more than half our time on one move, unless it's the last before the time control
+    let max = if moves_to_go == 1 {
+        available
+    } else {
+        available / 2
+    };
+
+    let soft = (available / moves_to_go + clock.increment * 3 / 4).min(max);
+    let hard = (soft * 4).min(max);
+    (soft, hard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn clock(time_ms: u64, increment_ms: u64, moves_to_go: Option<u32>) -> Clock {
+        Clock {
+            time: Duration::from_millis(time_ms),
+            increment: Duration::from_millis(increment_ms),
+            moves_to_go,
+        }
+    }
+
+    #[test]
+    fn allocates_a_share_of_remaining_time() {
+        let (soft, hard) = allocate(clock(60_050, 0, None));
+        assert_eq!(soft, Duration::from_secs(2));
+        assert_eq!(hard, Duration::from_secs(8));
+    }
+
+    #[test]
+    fn allocates_most_of_the_increment() {
+        let (soft, _) = allocate(clock(60_050, 2_000, None));
+        assert_eq!(soft, Duration::from_millis(3_500));
+    }
+
+    #[test]
+    fn uses_moves_to_go() {
+        let (soft, _) = allocate(clock(10_050, 0, Some(5)));
+        assert_eq!(soft, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn can_use_all_time_on_last_move_before_time_control() {
+        let (soft, hard) = allocate(clock(10_050, 0, Some(1)));
+        assert_eq!(soft, Duration::from_secs(10));
+        assert_eq!(hard, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn never_allocates_more_than_the_clock() {
+        let (soft, hard) = allocate(clock(1_000, 5_000, None));
+        assert!(soft <= hard);
+        assert!(hard < Duration::from_secs(1));
+
+        let (soft, hard) = allocate(clock(10, 0, None));
+        assert_eq!(soft, Duration::ZERO);
+        assert_eq!(hard, Duration::ZERO);
+    }
+
+    #[test]
+    fn without_time_limits_there_is_always_time_for_another_iteration() {
+        let mut time = TimeManager::new(&SearchLimits::depth(5));
+        assert!(!time.hard_limit_reached());
+        assert!(time.iteration_complete(None));
+
+        let mut time = TimeManager::new(&SearchLimits {
+            movetime: Some(Duration::ZERO),
+            infinite: true,
+            ..SearchLimits::default()
+        });
+        assert!(!time.hard_limit_reached());
+        assert!(time.iteration_complete(None));
+    }
+
+    #[test]
+    fn stops_iterating_after_movetime() {
+        let mut time = TimeManager::new(&SearchLimits::movetime(Duration::ZERO));
+        assert!(time.hard_limit_reached());
+        assert!(!time.iteration_complete(None));
+    }
+
+    #[test]
+    fn unstable_best_move_extends_soft_limit() {
+        let mut time = TimeManager::new(&SearchLimits::movetime(Duration::from_secs(1)));
+        time.soft_limit = Some(Duration::from_millis(100));
+        time.hard_limit = None;
+        time.start -= Duration::from_millis(150);
+
+        let e2e4 = "e2e4".parse().ok();
+        let d2d4 = "d2d4".parse().ok();
+
+        assert!(!time.iteration_complete(e2e4));
+        assert!(time.iteration_complete(d2d4));
+        assert!(!time.iteration_complete(d2d4));
+    }
+
+    #[test]
+    fn infinite_search_is_not_limited() {
+        assert!(SearchLimits::depth(3).is_limited());
+        assert!(!SearchLimits::default().is_limited());
+        assert!(!SearchLimits {
+            infinite: true,
+            ..SearchLimits::depth(3)
+        }
+        .is_limited());
+    }
+}
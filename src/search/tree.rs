@@ -1,5 +1,5 @@
 use crate::search::ttable::{Node, TranspositionTable};
-use crate::{Board, Move};
+use crate::{Board, MoveKind, MoveList};
 use serde::Serialize;
 use std::collections::BTreeMap;
 
@@ -11,13 +11,14 @@ pub struct SearchTree {
 
 impl SearchTree {
     pub fn from_table(board: &mut Board, table: &TranspositionTable, depth: u16) -> Self {
-        let node = table.get(&board.key());
+        let node = table.get(board.hash());
         let mut children = BTreeMap::new();
 
         if node.is_some() && depth > 0 {
-            let moves: Vec<Move> = board.moves().collect();
+            let mut moves = MoveList::new();
+            board.generate(MoveKind::Legal, &mut moves);
 
-            for mov in moves {
+            for mov in &moves {
                 let pmov = board.make_move(mov);
                 let child = Self::from_table(board, table, depth - 1);
                 board.unmake_move(pmov);
@@ -1,126 +1,202 @@
-use crate::search::LOW_SCORE;
-use crate::{Bitboard, Board, BoardFlags, Move, PieceTypeV, PlayerV};
-use enum_map::EnumMap;
+use crate::{Board, Move, PackedMove};
 use serde::Serialize;
-use std::collections::hash_map::DefaultHasher;
 use std::collections::HashSet;
-use std::hash::{Hash, Hasher};
-use std::sync::atomic::AtomicU64;
+use std::convert::TryFrom;
 use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicU64, AtomicU8};
 
-/// Table of moves, the key represents the game-state
+/// Where the search generation is stored in a packed entry, see [Node]
+const GENERATION_SHIFT: u32 = 42;
+const GENERATION_MASK: u64 = 0b11_1111;
+/// Generation of empty entries. Searches count from 1 and skip it when they wrap, so any node,
+/// even one that packs to zero, can be told apart from an empty slot.
+const EMPTY_GENERATION: u8 = 0;
+
+/// Number of entries sharing an index. The first ones prefer to keep deep entries, the last is
+/// always replaced.
+const BUCKET_SIZE: usize = 4;
+
+/// Entries that share an index, sized to fit in one cache line
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket([Entry; BUCKET_SIZE]);
+
+/// The hash key (XORed with the packed [Node], so torn writes from other threads are detected) and
+/// the packed node
+#[derive(Default)]
+struct Entry(AtomicU64, AtomicU64);
+
+impl Entry {
+    fn load(&self) -> (u64, u64) {
+        let hnode = self.1.load(Relaxed);
+        (self.0.load(Relaxed) ^ hnode, hnode)
+    }
+
+    fn store(&self, hkey: u64, hnode: u64) {
+        self.0.store(hkey ^ hnode, Relaxed);
+        self.1.store(hnode, Relaxed);
+    }
+}
+
+/// Table of moves, keyed by the [Zobrist hash](Board::hash) of the game-state
 pub struct TranspositionTable {
-    table: Vec<(AtomicU64, AtomicU64)>,
+    table: Vec<Bucket>,
+    /// Incremented for every new search, so entries from earlier searches can be told apart. Never
+    /// [EMPTY_GENERATION].
+    generation: AtomicU8,
 }
 
 impl TranspositionTable {
+    /// Size of a single entry in bytes
+    pub const ENTRY_SIZE: usize = std::mem::size_of::<Entry>();
+
+    /// Create a table with room for about `size` entries
     pub fn new(size: usize) -> Self {
         Self {
             table: std::iter::repeat_with(Default::default)
-                .take(size)
+                .take((size / BUCKET_SIZE).max(1))
                 .collect(),
+            generation: AtomicU8::new(EMPTY_GENERATION + 1),
         }
     }
 
-    pub fn get(&self, key: &Key) -> Option<Node> {
-        let hkey: u64 = hash_key(key);
-        let index = hkey as usize % self.table.len();
-        let hnode = self.table[index].1.load(Relaxed);
-        if self.table[index].0.load(Relaxed) ^ hnode == hkey {
-            Some(hnode.into())
-        } else {
-            None
-        }
+    /// Mark the start of a new search. Entries from earlier searches are still used, but are
+    /// replaced first and no longer count towards [hashfull](Self::hashfull).
+    pub fn new_search(&self) {
+        let generation = self.generation() as u64 % GENERATION_MASK + 1;
+        self.generation.store(generation as u8, Relaxed);
     }
 
-    pub fn insert(&self, key: Key, node: Node) {
-        let hkey: u64 = hash_key(&key);
-        let hnode: u64 = node.into();
-        let index = hkey as usize % self.table.len();
-        self.table[index].0.store(hkey ^ hnode, Relaxed);
-        self.table[index].1.store(hnode, Relaxed);
+    fn generation(&self) -> u8 {
+        self.generation.load(Relaxed)
     }
 
-    pub fn principal_variation(&self, board: &mut Board) -> Vec<Move> {
-        let mut pv = vec![];
-        let mut key_set = HashSet::new();
-        let mut adjust = 1;
+    /// Empty the table, forgetting everything previously searched
+    pub fn clear(&self) {
+        for bucket in &self.table {
+            for entry in &bucket.0 {
+                entry.store(0, (EMPTY_GENERATION as u64) << GENERATION_SHIFT);
+            }
+        }
+    }
 
-        'find_pv: loop {
-            // Negate score based on who is playing
-            adjust *= -1;
+    fn bucket(&self, hkey: u64) -> &Bucket {
+        &self.table[hkey as usize % self.table.len()]
+    }
 
-            let moves: Vec<Move> = board.moves().collect();
+    pub fn get(&self, hkey: u64) -> Option<Node> {
+        self.bucket(hkey).0.iter().find_map(|entry| {
+            let (entry_key, hnode) = entry.load();
+            if entry_key == hkey && is_occupied(hnode) {
+                Some(hnode.into())
+            } else {
+                None
+            }
+        })
+    }
 
-            let mut best_move = None;
-            let mut best_score = LOW_SCORE;
+    pub fn insert(&self, hkey: u64, mut node: Node) {
+        let bucket = self.bucket(hkey);
+        let generation = self.generation();
 
-            for mov in moves {
-                let pmov = board.make_move(mov);
-                let key = board.key();
-                board.unmake_move(pmov);
+        let existing = bucket.0.iter().find(|entry| {
+            let (entry_key, hnode) = entry.load();
+            entry_key == hkey && is_occupied(hnode)
+        });
 
-                // Check for loop
-                if !key_set.insert(key) {
-                    break 'find_pv;
+        let entry = match existing {
+            Some(entry) => {
+                if node.best_move.is_none() {
+                    // Keep the best move we already know about, it's still good for move ordering
+                    node.best_move = Node::from(entry.load().1).best_move;
                 }
-
-                if let Some(entry) = self.get(&key) {
-                    let score = entry.value * adjust;
-                    if entry.node_type == NodeType::PV && score > best_score {
-                        best_move = Some(mov);
-                        best_score = score;
-                    }
+                entry
+            }
+            None => {
+                let (depth_preferred, always_replace) = bucket.0.split_at(BUCKET_SIZE - 1);
+                // Replace the least valuable entry: empty, then from an old search, then shallowest
+                let (worst, worst_value) = depth_preferred
+                    .iter()
+                    .map(|entry| (entry, replacement_value(entry.load().1, generation)))
+                    .min_by_key(|(_, value)| *value)
+                    .unwrap();
+                if worst_value <= (true, true, node.depth) {
+                    worst
+                } else {
+                    &always_replace[0]
                 }
             }
+        };
 
-            if let Some(best) = best_move {
-                let pmov = board.make_move(best);
-                pv.push(pmov);
-            } else {
+        let hnode = u64::from(node) | (generation as u64) << GENERATION_SHIFT;
+        entry.store(hkey, hnode);
+    }
+
+    /// Approximate fraction of the table used by the current search, in permille.
+    ///
+    /// Only a sample of entries at the start of the table are checked.
+    pub fn hashfull(&self) -> u16 {
+        let sample = &self.table[..self.table.len().min(1000 / BUCKET_SIZE)];
+        let generation = self.generation();
+        let entries = sample.iter().flat_map(|bucket| bucket.0.iter());
+        let used = entries
+            .clone()
+            .filter(|entry| {
+                let hnode = entry.load().1;
+                is_occupied(hnode) && self::generation(hnode) == generation
+            })
+            .count();
+        (used * 1000 / entries.count().max(1)) as u16
+    }
+
+    /// Follow the best moves stored in the table, starting from the given position
+    pub fn principal_variation(&self, board: &mut Board) -> Vec<Move> {
+        let mut pv = vec![];
+        let mut hash_set = HashSet::new();
+
+        while let Some(mov) = self.get(board.hash()).and_then(|entry| entry.best_move) {
+            // Stop at repetitions, otherwise the PV would go on forever
+            if !hash_set.insert(board.hash()) {
                 break;
             }
+
+            // Another position might share an entry, so only follow legal moves
+            if !board.is_pseudo_legal(mov) {
+                break;
+            }
+            match board.make_if_legal(mov) {
+                Some(pmov) => pv.push(pmov),
+                None => break,
+            }
         }
 
         for pmov in pv.iter().rev() {
             board.unmake_move(*pmov);
         }
 
-        // Return a totally random move if we couldn't find anything.
-        // This can happen if search is stopped very quickly.
-        if pv.is_empty() {
-            if let Some(mov) = board.moves().next() {
-                return vec![mov];
-            }
-        }
-
         pv.into_iter().map(|pmov| pmov.mov).collect()
     }
 }
 
-pub type Key = (
-    EnumMap<PieceTypeV, Bitboard>,
-    EnumMap<PlayerV, Bitboard>,
-    PlayerV,
-    BoardFlags,
-);
-
-fn hash_key(key: &Key) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    key.hash(&mut hasher);
-    hasher.finish()
-}
-
+/// An entry in the table.
+///
+/// Packed into a `u64` as the value (32 bits), depth (8 bits), node type (2 bits), search
+/// generation (6 bits) and best move (16 bits).
 #[derive(Debug, Copy, Clone, Serialize, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Node {
     pub depth: u16,
     pub node_type: NodeType,
     pub value: i32,
+    /// Best move found, or the move that caused a cut-off
+    pub best_move: Option<Move>,
 }
 
 impl From<Node> for u64 {
     fn from(n: Node) -> u64 {
-        n.value as u32 as u64 | (n.depth as u64) << 32 | (n.node_type as u64) << 48
+        n.value as u32 as u64
+            | (n.depth.min(u8::MAX as u16) as u64) << 32
+            | (n.node_type as u64) << 40
+            | (pack_move(n.best_move) as u64) << 48
     }
 }
 
@@ -128,12 +204,38 @@ impl From<u64> for Node {
     fn from(n: u64) -> Self {
         Self {
             value: n as u32 as i32,
-            depth: (n >> 32) as u16,
-            node_type: (n >> 48).into(),
+            depth: (n >> 32) as u8 as u16,
+            node_type: ((n >> 40) & 0b11).into(),
+            best_move: unpack_move((n >> 48) as u16),
         }
     }
 }
 
+fn generation(hnode: u64) -> u8 {
+    ((hnode >> GENERATION_SHIFT) & GENERATION_MASK) as u8
+}
+
+fn is_occupied(hnode: u64) -> bool {
+    generation(hnode) != EMPTY_GENERATION
+}
+
+/// How much we'd like to keep a packed node, compared to others in its bucket
+fn replacement_value(hnode: u64, generation: u8) -> (bool, bool, u16) {
+    let occupied = is_occupied(hnode);
+    let current = self::generation(hnode) == generation;
+    (occupied, current, Node::from(hnode).depth)
+}
+
+/// Zero means no move, since no move packs to zero. Moves that can't be played aren't kept.
+fn pack_move(mov: Option<Move>) -> u16 {
+    mov.and_then(|mov| PackedMove::try_from(mov).ok())
+        .map_or(0, PackedMove::bits)
+}
+
+fn unpack_move(packed: u16) -> Option<Move> {
+    (packed != 0).then(|| PackedMove::from_bits(packed).into())
+}
+
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Serialize)]
 pub enum NodeType {
     /// Principal variation node, fully explored and value is exact
@@ -154,3 +256,180 @@ impl From<u64> for NodeType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mov;
+    use crate::strategies::*;
+    use pretty_assertions::assert_eq;
+    use proptest::proptest;
+
+    fn node(best_move: Option<Move>) -> Node {
+        Node {
+            depth: 3,
+            node_type: NodeType::PV,
+            value: -42,
+            best_move,
+        }
+    }
+
+    #[test]
+    fn promotions_are_stored() {
+        let promotion = Some(mov!(b7a8n));
+        assert_eq!(Node::from(u64::from(node(promotion))), node(promotion));
+    }
+
+    #[test]
+    fn principal_variation_follows_best_moves() {
+        let table = TranspositionTable::new(1024);
+        let mut board = Board::default();
+        let moves = [mov!(e2e4), mov!(e7e5), mov!(g1f3)];
+
+        let mut pmovs = vec![];
+        for mov in moves.iter() {
+            table.insert(board.hash(), node(Some(*mov)));
+            pmovs.push(board.make_move(*mov));
+        }
+        for pmov in pmovs.into_iter().rev() {
+            board.unmake_move(pmov);
+        }
+
+        assert_eq!(table.principal_variation(&mut board), moves.to_vec());
+        assert_eq!(board, Board::default());
+    }
+
+    #[test]
+    fn principal_variation_ignores_illegal_moves() {
+        let table = TranspositionTable::new(1024);
+        let mut board = Board::default();
+        table.insert(board.hash(), node(Some(mov!(e2e5))));
+        assert_eq!(table.principal_variation(&mut board), vec![]);
+    }
+
+    #[test]
+    fn inserting_without_a_best_move_keeps_the_old_one() {
+        let table = TranspositionTable::new(1024);
+        table.insert(1, node(Some(mov!(e2e4))));
+        table.insert(1, node(None));
+        assert_eq!(table.get(1).unwrap().best_move, Some(mov!(e2e4)));
+    }
+
+    #[test]
+    fn hashfull_only_counts_the_current_search() {
+        let table = TranspositionTable::new(4);
+        table.insert(1, node(None));
+        assert_eq!(table.hashfull(), 250);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+        assert!(table.get(1).is_some());
+    }
+
+    #[test]
+    fn nodes_that_pack_to_zero_are_stored_after_the_generation_wraps() {
+        let table = TranspositionTable::new(BUCKET_SIZE);
+        let zero = Node {
+            depth: 0,
+            node_type: NodeType::PV,
+            value: 0,
+            best_move: None,
+        };
+        assert_eq!(u64::from(zero), 0);
+
+        for _ in 0..=GENERATION_MASK {
+            table.new_search();
+            assert_ne!(table.generation(), EMPTY_GENERATION);
+        }
+        table.insert(1, zero);
+        assert_eq!(table.get(1), Some(zero));
+        assert_eq!(table.hashfull(), 1000 / BUCKET_SIZE as u16);
+
+        table.clear();
+        assert_eq!(table.get(1), None);
+    }
+
+    #[test]
+    fn deep_entries_are_not_replaced_by_shallow_ones() {
+        let table = TranspositionTable::new(BUCKET_SIZE);
+        let deep = Node {
+            depth: 10,
+            ..node(None)
+        };
+        for hkey in 1..BUCKET_SIZE as u64 {
+            table.insert(hkey, deep);
+        }
+
+        table.insert(100, node(None));
+        table.insert(101, node(None));
+
+        for hkey in 1..BUCKET_SIZE as u64 {
+            assert_eq!(table.get(hkey), Some(deep));
+        }
+        assert_eq!(table.get(100), None);
+        assert_eq!(table.get(101), Some(node(None)));
+    }
+
+    #[test]
+    fn entries_from_earlier_searches_are_replaced_first() {
+        let table = TranspositionTable::new(BUCKET_SIZE);
+        let deep = Node {
+            depth: 10,
+            ..node(None)
+        };
+        for hkey in 1..BUCKET_SIZE as u64 {
+            table.insert(hkey, deep);
+        }
+
+        table.new_search();
+        for hkey in 100..100 + BUCKET_SIZE as u64 - 1 {
+            table.insert(hkey, node(None));
+        }
+
+        for hkey in 1..BUCKET_SIZE as u64 {
+            assert_eq!(table.get(hkey), None);
+            assert_eq!(table.get(hkey + 99), Some(node(None)));
+        }
+    }
+
+    #[test]
+    fn existing_entries_are_updated_in_place() {
+        let table = TranspositionTable::new(BUCKET_SIZE);
+        table.insert(
+            1,
+            Node {
+                depth: 10,
+                ..node(None)
+            },
+        );
+        table.insert(1, node(None));
+        assert_eq!(table.get(1), Some(node(None)));
+        assert_eq!(table.hashfull(), 1000 / BUCKET_SIZE as u16);
+    }
+
+    proptest! {
+        #[test]
+        fn nodes_can_be_packed_into_a_u64(
+            (_, mov) in board_and_move(legal_board(0..20)),
+            depth in 0..=u8::MAX as u16,
+            value in -2_000_000..2_000_000,
+        ) {
+            let node = Node { depth, node_type: NodeType::Cut, value, best_move: Some(mov) };
+            assert_eq!(Node::from(u64::from(node)), node);
+        }
+
+        #[test]
+        fn principal_variation_is_always_legal(state in legal_game_state(0..20)) {
+            let table = TranspositionTable::new(64);
+            let mut board = state.board.clone();
+            for mov in board.moves().collect::<Vec<_>>() {
+                table.insert(board.hash(), node(Some(mov)));
+            }
+            let pv = table.principal_variation(&mut board);
+            assert_eq!(board, state.board);
+            for mov in pv {
+                assert!(board.check_legal(mov));
+                board.make_move(mov);
+            }
+        }
+    }
+}
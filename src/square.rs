@@ -156,7 +156,7 @@ impl<T> SquareMap<T> {
         SquareMap(values)
     }
 
-    pub fn from<F: Fn(Square) -> T>(f: F) -> Self {
+    pub fn from<F: FnMut(Square) -> T>(mut f: F) -> Self {
         let arr = array_init::array_init(|i| f(Square(i as u8)));
         SquareMap::new(arr)
     }
@@ -45,7 +45,7 @@ pub fn arb_pieces() -> impl Strategy<Value = [[Option<PieceV>; 8]; 8]> {
 }
 
 pub fn arb_piece_type() -> impl Strategy<Value = PieceTypeV> {
-    let mut pawns = std::iter::repeat(PieceTypeV::Pawn).take(8).collect();
+    let mut pawns = std::iter::repeat_n(PieceTypeV::Pawn, 8).collect();
     let mut types = vec![
         PieceTypeV::King,
         PieceTypeV::Queen,
@@ -88,7 +88,7 @@ pub fn board_and_move(
     boards: impl Strategy<Value = Board>,
 ) -> impl Strategy<Value = (Board, Move)> {
     boards
-        .prop_filter_map("stalemate", |mut board| {
+        .prop_filter_map("stalemate", |board| {
             let moves: Vec<Move> = board.moves().collect();
             if moves.is_empty() {
                 None